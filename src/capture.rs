@@ -0,0 +1,393 @@
+//! Capture of the firmware framebuffer
+//!
+//! Pixels are read through `/dev/mem` and converted to 8 bits RGB,
+//! which can be written as PPM, or as PNG with the `png` feature.
+//!
+
+use std::io::{self, Write};
+use std::mem::size_of;
+
+use crate::display::{self, DisplaySettings};
+use crate::error::{Error, Result};
+use crate::framebuffer::{self, Framebuffer, PixelOrder, Rgb};
+use crate::kernel::{rpi_firmware_property, rpi_firmware_property_batch, PropertyTag};
+use crate::mailbox::Mailbox;
+use crate::message;
+use crate::mmap::MemoryMap;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Layout of the pixels shown by the firmware
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    pub width: u32,
+    pub height: u32,
+    /// Bits per pixel
+    pub depth: u32,
+    /// Bytes per line
+    pub pitch: u32,
+    pub pixel_order: PixelOrder,
+    /// Position of the displayed area in the virtual buffer
+    pub virtual_offset: (u32, u32),
+}
+
+impl Geometry {
+    /// Read the geometry of the current framebuffer
+    pub fn read(mb: &Mailbox) -> Result<Self> {
+        use message::*;
+
+        let mut physical = fb_physical_width_height::Message {
+            in_: fb_physical_width_height::In {
+                width: 0,
+                height: 0,
+            },
+        };
+        let mut depth = fb_depth::Message {
+            in_: fb_depth::In { depth: 0 },
+        };
+        let mut pitch = fb_pitch::Message {
+            out: fb_pitch::Out { pitch: 0 },
+        };
+        let mut pixel_order = fb_pixel_order::Message {
+            in_: fb_pixel_order::In { state: 0 },
+        };
+        let mut virtual_offset = fb_virtual_offset::Message {
+            in_: fb_virtual_offset::In { x: 0, y: 0 },
+        };
+        let mut tags = unsafe {
+            [
+                PropertyTag::new(
+                    RPI_FIRMWARE_FRAMEBUFFER_GET_PHYSICAL_WIDTH_HEIGHT,
+                    &mut physical,
+                    size_of::<fb_physical_width_height::Out>(),
+                ),
+                PropertyTag::new(
+                    RPI_FIRMWARE_FRAMEBUFFER_GET_DEPTH,
+                    &mut depth,
+                    size_of::<fb_depth::Out>(),
+                ),
+                PropertyTag::new(
+                    RPI_FIRMWARE_FRAMEBUFFER_GET_PITCH,
+                    &mut pitch,
+                    size_of::<fb_pitch::Out>(),
+                ),
+                PropertyTag::new(
+                    RPI_FIRMWARE_FRAMEBUFFER_GET_PIXEL_ORDER,
+                    &mut pixel_order,
+                    size_of::<fb_pixel_order::Out>(),
+                ),
+                PropertyTag::new(
+                    RPI_FIRMWARE_FRAMEBUFFER_GET_VIRTUAL_OFFSET,
+                    &mut virtual_offset,
+                    size_of::<fb_virtual_offset::Out>(),
+                ),
+            ]
+        };
+        rpi_firmware_property_batch(mb, &mut tags)?;
+        unsafe {
+            Ok(Geometry {
+                width: physical.out.width,
+                height: physical.out.height,
+                depth: depth.out.depth,
+                pitch: pitch.out.pitch,
+                pixel_order: PixelOrder::from_u32(pixel_order.out.state).unwrap_or(PixelOrder::Bgr),
+                virtual_offset: (virtual_offset.out.x, virtual_offset.out.y),
+            })
+        }
+    }
+
+    /// Geometry of the framebuffer shown on a display
+    pub fn from_settings(settings: &DisplaySettings, pixel_order: PixelOrder) -> Self {
+        Geometry {
+            width: settings.width,
+            height: settings.height,
+            depth: settings.depth,
+            pitch: settings.pitch,
+            pixel_order,
+            virtual_offset: settings.virtual_offset,
+        }
+    }
+
+    /// Offset in bytes of the first displayed pixel from the start of the buffer
+    pub fn offset(&self) -> usize {
+        let (x, y) = self.virtual_offset;
+        y as usize * self.pitch as usize + x as usize * self.depth as usize / 8
+    }
+
+    /// Bytes from the first to the last displayed pixel
+    pub fn len(&self) -> usize {
+        match self.height {
+            0 => 0,
+            h => {
+                (h as usize - 1) * self.pitch as usize
+                    + self.width as usize * self.depth as usize / 8
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// 8 bits RGB image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbImage {
+    pub width: u32,
+    pub height: u32,
+    /// `width * height * 3` bytes in row major order
+    pub data: Vec<u8>,
+}
+
+impl RgbImage {
+    /// Convert the displayed area of `pixels` laid out as `geometry`
+    ///
+    /// With `PixelOrder::Rgb` the first byte (or the high bits for 16 bits depth) is red.
+    /// `palette` is required for 8 bits depth.
+    pub fn convert(geometry: &Geometry, pixels: &[u8], palette: Option<&[Rgb]>) -> Result<Self> {
+        let bytes = match geometry.depth {
+            8 if palette.is_some() => 1,
+            16 => 2,
+            24 => 3,
+            32 => 4,
+            depth => return Err(Error::UnsupportedDepth { depth }),
+        };
+        let required = geometry.offset() + geometry.len();
+        if pixels.len() < required {
+            return Err(Error::BufferTooSmall {
+                size: pixels.len(),
+                required,
+            });
+        }
+        let pixels = &pixels[geometry.offset()..];
+        let mut data = Vec::with_capacity(geometry.width as usize * geometry.height as usize * 3);
+        for y in 0..geometry.height as usize {
+            let line = &pixels[y * geometry.pitch as usize..];
+            for p in line[..geometry.width as usize * bytes].chunks_exact(bytes) {
+                let (first, g, last) = match bytes {
+                    1 => {
+                        let c = palette.unwrap().get(p[0] as usize).copied();
+                        let c = c.unwrap_or_default();
+                        data.extend_from_slice(&[c.r, c.g, c.b]);
+                        continue;
+                    }
+                    2 => {
+                        let v = u16::from_le_bytes([p[0], p[1]]);
+                        let hi = (v >> 11) as u8 & 0x1f;
+                        let mid = (v >> 5) as u8 & 0x3f;
+                        let lo = v as u8 & 0x1f;
+                        (hi << 3 | hi >> 2, mid << 2 | mid >> 4, lo << 3 | lo >> 2)
+                    }
+                    _ => (p[0], p[1], p[2]),
+                };
+                let (r, b) = match geometry.pixel_order {
+                    PixelOrder::Rgb => (first, last),
+                    PixelOrder::Bgr => (last, first),
+                };
+                data.extend_from_slice(&[r, g, b]);
+            }
+        }
+        Ok(RgbImage {
+            width: geometry.width,
+            height: geometry.height,
+            data,
+        })
+    }
+
+    /// Write as binary PPM (P6)
+    pub fn write_ppm<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        w.write_all(&self.data)
+    }
+
+    /// Write as PNG
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        Ok(writer.finish()?)
+    }
+}
+
+/// Capture the displayed area, laid out as `geometry`, of the buffer at `bus_address`
+fn capture_at(mb: &Mailbox, geometry: &Geometry, bus_address: u32) -> Result<RgbImage> {
+    let palette = match geometry.depth {
+        8 => Some(framebuffer::get_palette(mb)?),
+        _ => None,
+    };
+    let map = MemoryMap::new(bus_address, geometry.offset() + geometry.len())?;
+    RgbImage::convert(geometry, map.as_slice(), palette.as_deref())
+}
+
+fn get_pixel_order(mb: &Mailbox) -> Result<PixelOrder> {
+    use message::fb_pixel_order::*;
+
+    let mut msg = Message {
+        in_: In { state: 0 },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_GET_PIXEL_ORDER,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(PixelOrder::from_u32(msg.out.state).unwrap_or(PixelOrder::Bgr)) }
+}
+
+/// Address of the buffer the firmware currently shows
+///
+/// The firmware answers an allocation with the existing buffer when one is
+/// already allocated, as it is once the console is up.
+fn get_current_buffer(mb: &Mailbox) -> Result<u32> {
+    use message::fb_allocate::*;
+
+    let mut msg = Message {
+        in_: In { alignment: 0 },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_ALLOCATE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    match unsafe { msg.out.base } {
+        0 => Err(Error::FramebufferAllocationFailed),
+        base => Ok(base),
+    }
+}
+
+/// Whether `err` is how firmware without multi-display support rejects its tags
+fn is_unsupported(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::RequestFailed { .. } | Error::ReqRespSizeBit { .. }
+    )
+}
+
+/// Capture what the firmware framebuffer shows on the display `display_num`
+///
+/// The buffer is located through `display::get_display_settings`. The pixel order
+/// and, for 8 bits depth, the palette are per display, so `display_num` is
+/// selected to read them and stays selected.
+pub fn capture_display(mb: &Mailbox, display_num: u32) -> Result<RgbImage> {
+    let settings = display::get_display_settings(mb, display_num)?;
+    display::set_display_num(mb, display_num)?;
+    let geometry = Geometry::from_settings(&settings, get_pixel_order(mb)?);
+    capture_at(mb, &geometry, settings.bus_address)
+}
+
+/// Capture what the firmware framebuffer shows on the first display
+///
+/// Firmware without multi-display support has a single framebuffer,
+/// which is captured instead.
+pub fn capture(mb: &Mailbox) -> Result<RgbImage> {
+    match capture_display(mb, 0) {
+        Err(err) if is_unsupported(&err) => {
+            let geometry = Geometry::read(mb)?;
+            capture_at(mb, &geometry, get_current_buffer(mb)?)
+        }
+        res => res,
+    }
+}
+
+/// Capture the displayed area of `fb`
+///
+/// The display of `fb`, if any, is selected and stays selected.
+pub fn capture_framebuffer(fb: &Framebuffer) -> Result<RgbImage> {
+    fb.select_display()?;
+    let geometry = Geometry::read(fb.mailbox())?;
+    capture_at(fb.mailbox(), &geometry, fb.bus_address())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn geometry(depth: u32, pixel_order: PixelOrder) -> Geometry {
+        Geometry {
+            width: 2,
+            height: 2,
+            depth,
+            pitch: 16,
+            pixel_order,
+            virtual_offset: (0, 0),
+        }
+    }
+
+    #[test]
+    fn convert_32bpp() {
+        let mut pixels = vec![0u8; 32];
+        pixels[..8].copy_from_slice(&[1, 2, 3, 0xff, 4, 5, 6, 0xff]);
+        pixels[16..24].copy_from_slice(&[7, 8, 9, 0xff, 10, 11, 12, 0xff]);
+
+        let rgb = RgbImage::convert(&geometry(32, PixelOrder::Rgb), &pixels, None).unwrap();
+        assert_eq!(rgb.data, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        let bgr = RgbImage::convert(&geometry(32, PixelOrder::Bgr), &pixels, None).unwrap();
+        assert_eq!(bgr.data, vec![3, 2, 1, 6, 5, 4, 9, 8, 7, 12, 11, 10]);
+    }
+
+    #[test]
+    fn convert_16bpp() {
+        let mut pixels = vec![0u8; 32];
+        pixels[..2].copy_from_slice(&0xf800u16.to_le_bytes());
+        pixels[2..4].copy_from_slice(&0x07e0u16.to_le_bytes());
+
+        let rgb = RgbImage::convert(&geometry(16, PixelOrder::Rgb), &pixels, None).unwrap();
+        assert_eq!(&rgb.data[..6], &[0xff, 0, 0, 0, 0xff, 0]);
+        let bgr = RgbImage::convert(&geometry(16, PixelOrder::Bgr), &pixels, None).unwrap();
+        assert_eq!(&bgr.data[..3], &[0, 0, 0xff]);
+    }
+
+    #[test]
+    fn short_buffer_and_depth() {
+        let pixels = vec![0u8; 8];
+        assert!(matches!(
+            RgbImage::convert(&geometry(32, PixelOrder::Rgb), &pixels, None),
+            Err(Error::BufferTooSmall { .. })
+        ));
+        assert!(matches!(
+            RgbImage::convert(&geometry(8, PixelOrder::Rgb), &pixels, None),
+            Err(Error::UnsupportedDepth { depth: 8 })
+        ));
+    }
+
+    #[test]
+    fn unsupported_errors() {
+        assert!(is_unsupported(&Error::RequestFailed { code: 0x8000_0001 }));
+        assert!(is_unsupported(&Error::ReqRespSizeBit { req_resp_size: 0 }));
+        assert!(!is_unsupported(&Error::FramebufferAllocationFailed));
+    }
+
+    #[test]
+    fn geometry_from_settings() {
+        let settings = DisplaySettings {
+            display_num: 2,
+            width: 1920,
+            height: 1080,
+            depth: 32,
+            pitch: 7680,
+            virtual_size: (1920, 2160),
+            virtual_offset: (0, 1080),
+            bus_address: 0xfe00_0000,
+        };
+        let geometry = Geometry::from_settings(&settings, PixelOrder::Rgb);
+        assert_eq!(geometry.offset(), 1080 * 7680);
+        assert_eq!(geometry.len(), 1080 * 7680);
+    }
+
+    #[test]
+    fn ppm_header() {
+        let image = RgbImage {
+            width: 1,
+            height: 1,
+            data: vec![1, 2, 3],
+        };
+        let mut out = Vec::new();
+        image.write_ppm(&mut out).unwrap();
+        assert_eq!(out, b"P6\n1 1\n255\n\x01\x02\x03");
+    }
+}
@@ -0,0 +1,217 @@
+//! Scoped change of clock rates
+//!
+//! The firmware is accessed through `governor::Backend`, implemented by `Mailbox`.
+//!
+
+use log::*;
+
+use crate::error::{Error, Result};
+use crate::governor::Backend;
+use crate::mailbox::Mailbox;
+use crate::raspberrypi_firmware::rpi_firmware_clk_id;
+
+/// Restores the rate of a clock which was changed by `ClockRateGuard::new`
+///
+/// The original rate is restored when the guard is dropped or rolled back.
+/// Call `commit` to keep the new rate.
+#[derive(Debug)]
+pub struct ClockRateGuard<'a, B: Backend + ?Sized = Mailbox> {
+    backend: &'a B,
+    clock_id: rpi_firmware_clk_id,
+    original: u32,
+    rate: u32,
+    skip_setting_turbo: bool,
+    armed: bool,
+}
+
+impl<'a, B: Backend + ?Sized> ClockRateGuard<'a, B> {
+    /// Set the rate of `clock_id` to `rate` remembering the current rate
+    ///
+    /// The rate returned by the firmware must be within `tolerance` Hz of `rate`,
+    /// otherwise the original rate is restored and `Error::ClockRateMismatch` is returned.
+    pub fn new(
+        backend: &'a B,
+        clock_id: rpi_firmware_clk_id,
+        rate: u32,
+        skip_setting_turbo: bool,
+        tolerance: u32,
+    ) -> Result<Self> {
+        let original = backend.clock_rate(clock_id)?;
+        let mut guard = ClockRateGuard {
+            backend,
+            clock_id,
+            original,
+            rate: original,
+            skip_setting_turbo,
+            armed: true,
+        };
+        guard.rate = backend.set_clock_rate(clock_id, rate, skip_setting_turbo)?;
+        if guard.rate.abs_diff(rate) > tolerance {
+            let actual = guard.rate;
+            guard.rollback()?;
+            return Err(Error::ClockRateMismatch {
+                clock_id: clock_id as u32,
+                requested: rate,
+                actual,
+            });
+        }
+        Ok(guard)
+    }
+
+    pub fn clock_id(&self) -> rpi_firmware_clk_id {
+        self.clock_id
+    }
+
+    /// The rate before the guard was created
+    pub fn original_rate(&self) -> u32 {
+        self.original
+    }
+
+    /// The rate reported by the firmware
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Keep the new rate
+    pub fn commit(mut self) -> u32 {
+        self.armed = false;
+        self.rate
+    }
+
+    /// Restore the original rate now
+    ///
+    /// Returns the rate reported by the firmware.
+    pub fn rollback(mut self) -> Result<u32> {
+        self.armed = false;
+        self.restore()
+    }
+
+    fn restore(&self) -> Result<u32> {
+        self.backend
+            .set_clock_rate(self.clock_id, self.original, self.skip_setting_turbo)
+    }
+}
+
+impl<'a, B: Backend + ?Sized> Drop for ClockRateGuard<'a, B> {
+    fn drop(&mut self) {
+        if self.armed {
+            if let Err(err) = self.restore() {
+                error!(
+                    "failed to restore the rate of clock {:?} to {}: {}",
+                    self.clock_id, self.original, err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    use crate::raspberrypi_firmware::rpi_firmware_clk_id::*;
+    use crate::throttled;
+
+    /// ARM clock which clamps the rates to `max`
+    struct FakeBackend {
+        rate: Cell<u32>,
+        max: u32,
+        sets: RefCell<Vec<u32>>,
+    }
+
+    impl FakeBackend {
+        fn new(rate: u32, max: u32) -> Self {
+            FakeBackend {
+                rate: Cell::new(rate),
+                max,
+                sets: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Backend for FakeBackend {
+        fn temperature(&self) -> Result<u32> {
+            Ok(50_000)
+        }
+        fn throttled(&self) -> Result<throttled::Flags> {
+            Ok(throttled::Flags::empty())
+        }
+        fn voltage(&self, _: u32) -> Result<u32> {
+            Ok(1_200_000)
+        }
+        fn clock_rate(&self, _: rpi_firmware_clk_id) -> Result<u32> {
+            Ok(self.rate.get())
+        }
+        fn min_clock_rate(&self, _: rpi_firmware_clk_id) -> Result<u32> {
+            Ok(600_000_000)
+        }
+        fn max_clock_rate(&self, _: rpi_firmware_clk_id) -> Result<u32> {
+            Ok(self.max)
+        }
+        fn set_clock_rate(&self, _: rpi_firmware_clk_id, rate: u32, _: bool) -> Result<u32> {
+            self.sets.borrow_mut().push(rate);
+            self.rate.set(rate.min(self.max));
+            Ok(self.rate.get())
+        }
+    }
+
+    #[test]
+    fn drop_restores() {
+        let backend = FakeBackend::new(1_500_000_000, 1_800_000_000);
+        {
+            let guard =
+                ClockRateGuard::new(&backend, RPI_FIRMWARE_ARM_CLK_ID, 1_000_000_000, false, 0)
+                    .unwrap();
+            assert_eq!(guard.original_rate(), 1_500_000_000);
+            assert_eq!(guard.rate(), 1_000_000_000);
+            assert_eq!(backend.rate.get(), 1_000_000_000);
+        }
+        assert_eq!(backend.rate.get(), 1_500_000_000);
+    }
+
+    #[test]
+    fn commit_keeps_rate() {
+        let backend = FakeBackend::new(1_500_000_000, 1_800_000_000);
+        let guard = ClockRateGuard::new(&backend, RPI_FIRMWARE_ARM_CLK_ID, 1_000_000_000, false, 0)
+            .unwrap();
+        assert_eq!(guard.commit(), 1_000_000_000);
+        assert_eq!(backend.rate.get(), 1_000_000_000);
+        assert_eq!(*backend.sets.borrow(), vec![1_000_000_000]);
+    }
+
+    #[test]
+    fn rollback_restores_once() {
+        let backend = FakeBackend::new(1_500_000_000, 1_800_000_000);
+        let guard = ClockRateGuard::new(&backend, RPI_FIRMWARE_ARM_CLK_ID, 1_000_000_000, false, 0)
+            .unwrap();
+        assert_eq!(guard.rollback().unwrap(), 1_500_000_000);
+        assert_eq!(*backend.sets.borrow(), vec![1_000_000_000, 1_500_000_000]);
+    }
+
+    #[test]
+    fn tolerance() {
+        let backend = FakeBackend::new(1_500_000_000, 1_800_000_000);
+        let guard = ClockRateGuard::new(
+            &backend,
+            RPI_FIRMWARE_ARM_CLK_ID,
+            1_900_000_000,
+            false,
+            100_000_000,
+        )
+        .unwrap();
+        assert_eq!(guard.rate(), 1_800_000_000);
+        drop(guard);
+
+        let res = ClockRateGuard::new(&backend, RPI_FIRMWARE_ARM_CLK_ID, 2_000_000_000, false, 0);
+        assert!(matches!(
+            res,
+            Err(Error::ClockRateMismatch {
+                requested: 2_000_000_000,
+                actual: 1_800_000_000,
+                ..
+            })
+        ));
+        assert_eq!(backend.rate.get(), 1_500_000_000);
+    }
+}
@@ -0,0 +1,161 @@
+//! Kernel command line passed by the firmware
+//!
+
+use std::fmt;
+
+use crate::error::Result;
+use crate::kernel::rpi_firmware_property_varlen;
+use crate::mailbox::Mailbox;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Get the command line the firmware passes to the kernel
+///
+/// This may differ from `/proc/cmdline` if the bootloader edited it.
+pub fn get_command_line(mb: &Mailbox) -> Result<String> {
+    let mut buf = vec![0u8; 1024];
+    let len = rpi_firmware_property_varlen(mb, RPI_FIRMWARE_GET_COMMAND_LINE, &mut buf)?;
+    if buf.len() < len {
+        buf = vec![0u8; len];
+        rpi_firmware_property_varlen(mb, RPI_FIRMWARE_GET_COMMAND_LINE, &mut buf)?;
+    }
+    buf.truncate(len);
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Ok(String::from_utf8_lossy(&buf[..end]).into_owned())
+}
+
+/// A parameter of the command line
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Entry {
+    /// `name`
+    Flag(String),
+    /// `key=value`
+    KeyValue { key: String, value: String },
+}
+
+impl Entry {
+    pub fn key(&self) -> &str {
+        match self {
+            Entry::Flag(name) => name,
+            Entry::KeyValue { key, .. } => key,
+        }
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let quote = |s: &str| s.contains(char::is_whitespace);
+        match self {
+            Entry::Flag(name) if quote(name) => write!(f, "\"{}\"", name),
+            Entry::Flag(name) => write!(f, "{}", name),
+            Entry::KeyValue { key, value } if quote(value) => write!(f, "{}=\"{}\"", key, value),
+            Entry::KeyValue { key, value } => write!(f, "{}={}", key, value),
+        }
+    }
+}
+
+/// Parsed command line
+///
+/// Parameters are split as the kernel does: double quotes group words
+/// containing spaces and are removed, and the first `=` separates the key
+/// from the value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandLine {
+    entries: Vec<Entry>,
+}
+
+impl CommandLine {
+    pub fn parse(cmdline: &str) -> Self {
+        let mut entries = Vec::new();
+        let mut chars = cmdline.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+            let mut token = String::new();
+            let mut in_quote = false;
+            for c in chars.by_ref() {
+                match c {
+                    '"' => in_quote = !in_quote,
+                    c if c.is_whitespace() && !in_quote => break,
+                    c => token.push(c),
+                }
+            }
+            entries.push(match token.split_once('=') {
+                Some((key, value)) => Entry::KeyValue {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                },
+                None => Entry::Flag(token),
+            });
+        }
+        CommandLine { entries }
+    }
+
+    /// Parameters in order of appearance
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Value of the last `key=value`, which is the one the kernel uses
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().rev().find_map(|e| match e {
+            Entry::KeyValue { key: k, value } if k == key => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Values of all `key=value` in order, e.g. for `console`
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries.iter().filter_map(move |e| match e {
+            Entry::KeyValue { key: k, value } if k == key => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|e| matches!(e, Entry::Flag(n) if n == name))
+    }
+}
+
+impl fmt::Display for CommandLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, e) in self.entries.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", e)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_cmdline() {
+        let cmdline = CommandLine::parse(
+            " coherent_pool=1M console=ttyS0,115200 console=tty1 quiet \
+             video=HDMI-A-1:1920x1080M@60 dwc_otg.lpm_enable=0 cma=256M \
+             label=\"my disk\" \"spaced flag\" empty=",
+        );
+        assert_eq!(cmdline.entries().len(), 10);
+        assert_eq!(cmdline.get("console"), Some("tty1"));
+        assert_eq!(
+            cmdline.get_all("console").collect::<Vec<_>>(),
+            vec!["ttyS0,115200", "tty1"]
+        );
+        assert_eq!(cmdline.get("cma"), Some("256M"));
+        assert_eq!(cmdline.get("video"), Some("HDMI-A-1:1920x1080M@60"));
+        assert_eq!(cmdline.get("label"), Some("my disk"));
+        assert_eq!(cmdline.get("empty"), Some(""));
+        assert!(cmdline.has_flag("quiet"));
+        assert!(cmdline.has_flag("spaced flag"));
+        assert!(!cmdline.has_flag("cma"));
+        assert_eq!(CommandLine::parse(&cmdline.to_string()), cmdline);
+    }
+}
@@ -0,0 +1,203 @@
+//! Hardware cursor
+//!
+
+use std::mem::size_of;
+
+use log::*;
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::memflag;
+use crate::memory::LockedMemory;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Set the image of the cursor
+///
+/// pixels: bus address of `width` x `height` ARGB pixels
+pub fn set_cursor_info(
+    mb: &Mailbox,
+    width: u32,
+    height: u32,
+    pixels: u32,
+    hotspot: (u32, u32),
+) -> Result<()> {
+    use message::set_cursor_info::*;
+
+    let mut msg = Message {
+        in_: In {
+            width,
+            height,
+            unused: 0,
+            pixels,
+            hotspot_x: hotspot.0,
+            hotspot_y: hotspot.1,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_SET_CURSOR_INFO,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    match unsafe { msg.out.status } {
+        0 => Ok(()),
+        status => Err(Error::CursorRejected { status }),
+    }
+}
+
+/// Show or hide the cursor at (`x`, `y`) in display coordinates
+pub fn set_cursor_state(mb: &Mailbox, enable: bool, x: u32, y: u32) -> Result<()> {
+    use message::set_cursor_state::*;
+
+    let mut msg = Message {
+        in_: In {
+            enable: enable as u32,
+            x,
+            y,
+            flags: 0,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_SET_CURSOR_STATE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    match unsafe { msg.out.status } {
+        0 => Ok(()),
+        status => Err(Error::CursorRejected { status }),
+    }
+}
+
+/// Bytes of a `width` x `height` ARGB image, `None` on overflow
+fn image_size(width: u32, height: u32) -> Option<u32> {
+    width.checked_mul(height)?.checked_mul(4)
+}
+
+/// Hardware cursor whose image lives in VideoCore memory owned by the cursor
+///
+/// The cursor is created hidden and is hidden again on drop.
+#[derive(Debug)]
+pub struct Cursor<'a> {
+    mem: LockedMemory<'a>,
+    width: u32,
+    height: u32,
+    hotspot: (u32, u32),
+    position: (u32, u32),
+    visible: bool,
+}
+
+impl<'a> Cursor<'a> {
+    /// Upload `argb`, `width` x `height` pixels in row major order, as the cursor image
+    pub fn new(
+        mb: &'a Mailbox,
+        width: u32,
+        height: u32,
+        argb: &[u32],
+        hotspot: (u32, u32),
+    ) -> Result<Self> {
+        let size = match image_size(width, height) {
+            Some(size) if argb.len() * 4 == size as usize => size,
+            _ => return Err(Error::InvalidCursorImage { width, height }),
+        };
+        if width <= hotspot.0 || height <= hotspot.1 {
+            return Err(Error::InvalidCursorImage { width, height });
+        }
+        let mem = LockedMemory::new(mb, size, 4096, memflag::Flags::MEM_FLAG_L1_NONALLOCATING)?;
+        let mut cursor = Cursor {
+            mem,
+            width,
+            height,
+            hotspot,
+            position: (0, 0),
+            visible: false,
+        };
+        cursor.set_image(argb, hotspot)?;
+        Ok(cursor)
+    }
+
+    /// Replace the image keeping its size
+    pub fn set_image(&mut self, argb: &[u32], hotspot: (u32, u32)) -> Result<()> {
+        if argb.len() * 4 != self.mem.size() as usize
+            || self.width <= hotspot.0
+            || self.height <= hotspot.1
+        {
+            return Err(Error::InvalidCursorImage {
+                width: self.width,
+                height: self.height,
+            });
+        }
+        let mut map = self.mem.map()?;
+        for (dst, src) in map.as_mut_slice().chunks_exact_mut(4).zip(argb) {
+            dst.copy_from_slice(&src.to_le_bytes());
+        }
+        set_cursor_info(
+            self.mem.mailbox(),
+            self.width,
+            self.height,
+            self.mem.bus_address(),
+            hotspot,
+        )?;
+        self.hotspot = hotspot;
+        Ok(())
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn hotspot(&self) -> (u32, u32) {
+        self.hotspot
+    }
+
+    pub fn position(&self) -> (u32, u32) {
+        self.position
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Move the hotspot to (`x`, `y`)
+    pub fn move_to(&mut self, x: u32, y: u32) -> Result<()> {
+        set_cursor_state(self.mem.mailbox(), self.visible, x, y)?;
+        self.position = (x, y);
+        Ok(())
+    }
+
+    pub fn show(&mut self) -> Result<()> {
+        set_cursor_state(self.mem.mailbox(), true, self.position.0, self.position.1)?;
+        self.visible = true;
+        Ok(())
+    }
+
+    pub fn hide(&mut self) -> Result<()> {
+        set_cursor_state(self.mem.mailbox(), false, self.position.0, self.position.1)?;
+        self.visible = false;
+        Ok(())
+    }
+}
+
+impl<'a> Drop for Cursor<'a> {
+    fn drop(&mut self) {
+        if let Err(err) = self.hide() {
+            error!("failed to hide cursor: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn image_size_overflow() {
+        assert_eq!(image_size(64, 64), Some(64 * 64 * 4));
+        assert_eq!(image_size(0x8000, 0x8000), None);
+        assert_eq!(image_size(u32::MAX, 2), None);
+    }
+}
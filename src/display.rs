@@ -0,0 +1,154 @@
+//! Enumeration and selection of the attached displays
+//!
+//! Boards with several display outputs serve the framebuffer tags for the
+//! display selected by `set_display_num`.
+//!
+
+use std::mem::size_of;
+
+use crate::error::Result;
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Kind of a display output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DisplayId {
+    MainLcd,
+    AuxLcd,
+    Hdmi0,
+    Sdtv,
+    ForceLcd,
+    ForceTv,
+    ForceOther,
+    Hdmi1,
+    ForceTv2,
+    Unknown(u32),
+}
+
+impl DisplayId {
+    pub fn from_u32(id: u32) -> Self {
+        match id {
+            0 => DisplayId::MainLcd,
+            1 => DisplayId::AuxLcd,
+            2 => DisplayId::Hdmi0,
+            3 => DisplayId::Sdtv,
+            4 => DisplayId::ForceLcd,
+            5 => DisplayId::ForceTv,
+            6 => DisplayId::ForceOther,
+            7 => DisplayId::Hdmi1,
+            8 => DisplayId::ForceTv2,
+            id => DisplayId::Unknown(id),
+        }
+    }
+}
+
+/// Current mode of a display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplaySettings {
+    pub display_num: u32,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub pitch: u32,
+    pub virtual_size: (u32, u32),
+    pub virtual_offset: (u32, u32),
+    /// Bus address of the framebuffer shown on the display
+    pub bus_address: u32,
+}
+
+/// An attached display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Display {
+    /// Number passed to `set_display_num`
+    pub num: u32,
+    pub id: DisplayId,
+    pub settings: DisplaySettings,
+}
+
+pub fn get_num_displays(mb: &Mailbox) -> Result<u32> {
+    use message::fb_num_displays::*;
+
+    let mut msg = Message { in_: In };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_GET_NUM_DISPLAYS,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.num_displays) }
+}
+
+pub fn get_display_id(mb: &Mailbox, display_num: u32) -> Result<DisplayId> {
+    use message::fb_display_id::*;
+
+    let mut msg = Message {
+        in_: In { display_num },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_GET_DISPLAY_ID,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(DisplayId::from_u32(msg.out.display_id)) }
+}
+
+pub fn get_display_settings(mb: &Mailbox, display_num: u32) -> Result<DisplaySettings> {
+    use message::fb_display_settings::*;
+
+    let mut msg = Message {
+        in_: In { display_num },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_GET_DISPLAY_SETTINGS,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    let out = unsafe { msg.out };
+    Ok(DisplaySettings {
+        display_num: out.display_num,
+        width: out.width,
+        height: out.height,
+        depth: out.depth,
+        pitch: out.pitch as u32,
+        virtual_size: (out.virtual_width, out.virtual_height),
+        virtual_offset: (out.virtual_width_offset as u32, out.virtual_height_offset),
+        bus_address: out.fb_bus_address,
+    })
+}
+
+/// Direct the following framebuffer tags to `display_num`
+pub fn set_display_num(mb: &Mailbox, display_num: u32) -> Result<u32> {
+    use message::fb_set_display_num::*;
+
+    let mut msg = Message {
+        in_: In { display_num },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_SET_DISPLAY_NUM,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.display_num) }
+}
+
+/// List the attached displays
+pub fn enumerate(mb: &Mailbox) -> Result<Vec<Display>> {
+    (0..get_num_displays(mb)?)
+        .map(|num| {
+            Ok(Display {
+                num,
+                id: get_display_id(mb, num)?,
+                settings: get_display_settings(mb, num)?,
+            })
+        })
+        .collect()
+}
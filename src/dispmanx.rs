@@ -0,0 +1,95 @@
+//! Memory of dispmanx resources
+//!
+//! A resource converted to a `MemHandle` can be locked like memory from
+//! `mailbox_mem_alloc`, which allows sharing it without a copy.
+//!
+
+use std::mem::size_of;
+
+use log::*;
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::message;
+use crate::mmap::MemoryMap;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+use crate::{mailbox_mem_lock, mailbox_mem_unlock};
+
+/// VideoCore memory handle, as returned by `mailbox_mem_alloc`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemHandle(pub u32);
+
+impl MemHandle {
+    /// Lock the memory until the returned guard is dropped
+    pub fn lock<'a>(&self, mb: &'a Mailbox) -> Result<LockedHandle<'a>> {
+        let busaddr = mailbox_mem_lock(mb, self.0)?;
+        Ok(LockedHandle {
+            mb,
+            handle: *self,
+            busaddr,
+        })
+    }
+}
+
+/// Memory locked by `MemHandle::lock`, unlocked on drop
+///
+/// The memory itself is owned by the resource and is not released.
+#[derive(Debug)]
+pub struct LockedHandle<'a> {
+    mb: &'a Mailbox,
+    handle: MemHandle,
+    busaddr: u32,
+}
+
+impl<'a> LockedHandle<'a> {
+    pub fn handle(&self) -> MemHandle {
+        self.handle
+    }
+
+    pub fn bus_address(&self) -> u32 {
+        self.busaddr
+    }
+
+    /// Map `len` bytes of the memory into the process
+    pub fn map(&self, len: usize) -> Result<MemoryMap> {
+        MemoryMap::new(self.busaddr, len)
+    }
+}
+
+impl<'a> Drop for LockedHandle<'a> {
+    fn drop(&mut self) {
+        if let Err(err) = mailbox_mem_unlock(self.mb, self.busaddr) {
+            error!("failed to unlock 0x{:08x}: {}", self.busaddr, err);
+        }
+    }
+}
+
+impl From<MemHandle> for u32 {
+    fn from(handle: MemHandle) -> u32 {
+        handle.0
+    }
+}
+
+/// Get the memory handle of the dispmanx resource `resource`
+///
+/// Fails with `Error::UnknownResource` and the firmware status if `resource` is unknown.
+pub fn get_dispmanx_resource_mem_handle(mb: &Mailbox, resource: u32) -> Result<MemHandle> {
+    use message::dispmanx_resource_mem_handle::*;
+
+    let mut msg = Message {
+        in_: In { resource },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_DISPMANX_RESOURCE_MEM_HANDLE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    let out = unsafe { msg.out };
+    match out.status {
+        0 => Ok(MemHandle(out.mem_handle)),
+        status => Err(Error::UnknownResource { resource, status }),
+    }
+}
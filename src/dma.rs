@@ -0,0 +1,300 @@
+//! DMA channels left to the ARM by the firmware
+//!
+//! `Allocator` hands out channels from the firmware mask so that drivers in
+//! the same process do not collide on hard-coded channel numbers.
+//!
+
+use std::fmt;
+use std::mem::size_of;
+use std::sync::Mutex;
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Number of DMA channels of the SoC, excluding channel 15
+pub const NUM_CHANNELS: u32 = 15;
+
+/// Set of DMA channels, bit n for channel n
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ChannelSet(u32);
+
+impl ChannelSet {
+    pub fn from_mask(mask: u32) -> Self {
+        ChannelSet(mask & ((1 << NUM_CHANNELS) - 1))
+    }
+
+    pub fn mask(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, channel: u32) -> bool {
+        channel < NUM_CHANNELS && self.0 & (1 << channel) != 0
+    }
+
+    pub fn insert(&mut self, channel: u32) {
+        if channel < NUM_CHANNELS {
+            self.0 |= 1 << channel;
+        }
+    }
+
+    pub fn remove(&mut self, channel: u32) {
+        if channel < NUM_CHANNELS {
+            self.0 &= !(1 << channel);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Channels in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u32> {
+        let mask = self.0;
+        (0..NUM_CHANNELS).filter(move |&ch| mask & (1 << ch) != 0)
+    }
+}
+
+impl fmt::Display for ChannelSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, ch) in self.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", ch)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// Get the DMA channels usable by the ARM
+pub fn get_dma_channels(mb: &Mailbox) -> Result<ChannelSet> {
+    use message::dma_channels::*;
+
+    let mut msg = Message { in_: In };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_DMA_CHANNELS,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(ChannelSet::from_mask(msg.out.mask)) }
+}
+
+/// Family of the SoC, which decides the kind of each DMA channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Soc {
+    /// BCM2835, BCM2836 and BCM2837
+    Bcm283x,
+    Bcm2711,
+}
+
+impl Soc {
+    /// Decode the processor field of a new style board revision
+    ///
+    /// Old style revisions are all BCM2835.
+    pub fn from_board_revision(revision: u32) -> Option<Self> {
+        if revision & (1 << 23) == 0 {
+            return Some(Soc::Bcm283x);
+        }
+        match (revision >> 12) & 0xf {
+            0..=2 => Some(Soc::Bcm283x),
+            3 => Some(Soc::Bcm2711),
+            _ => None,
+        }
+    }
+
+    pub fn channel_kind(&self, channel: u32) -> Option<ChannelKind> {
+        match (self, channel) {
+            (_, 0..=6) => Some(ChannelKind::Normal),
+            (Soc::Bcm283x, 7..=14) => Some(ChannelKind::Lite),
+            (Soc::Bcm2711, 7..=10) => Some(ChannelKind::Lite),
+            (Soc::Bcm2711, 11..=14) => Some(ChannelKind::Dma4),
+            _ => None,
+        }
+    }
+
+    /// Channels of `kind` on this SoC
+    pub fn channels(&self, kind: ChannelKind) -> ChannelSet {
+        let mut set = ChannelSet::default();
+        for ch in 0..NUM_CHANNELS {
+            if self.channel_kind(ch) == Some(kind) {
+                set.insert(ch);
+            }
+        }
+        set
+    }
+}
+
+/// Kind of a DMA engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelKind {
+    /// Full featured engine with 2D mode
+    Normal,
+    /// Reduced bandwidth engine without 2D mode
+    Lite,
+    /// 40-bit address engine (BCM2711 only)
+    Dma4,
+}
+
+/// Process-local allocator of the DMA channels
+#[derive(Debug)]
+pub struct Allocator {
+    soc: Soc,
+    available: ChannelSet,
+    free: Mutex<ChannelSet>,
+}
+
+impl Allocator {
+    pub fn new(soc: Soc, available: ChannelSet) -> Self {
+        Allocator {
+            soc,
+            available,
+            free: Mutex::new(available),
+        }
+    }
+
+    /// Allocator over the channels the firmware leaves to the ARM
+    ///
+    /// Fails with `Error::UnsupportedSoc` if the board revision names a processor
+    /// whose channels are not known.
+    pub fn from_firmware(mb: &Mailbox) -> Result<Self> {
+        let available = get_dma_channels(mb)?;
+        let revision = crate::get_board_revision(mb)?;
+        let soc = Soc::from_board_revision(revision).ok_or(Error::UnsupportedSoc { revision })?;
+        Ok(Allocator::new(soc, available))
+    }
+
+    pub fn soc(&self) -> Soc {
+        self.soc
+    }
+
+    /// Channels usable by the ARM
+    pub fn available(&self) -> ChannelSet {
+        self.available
+    }
+
+    /// Channels not currently allocated
+    pub fn free(&self) -> ChannelSet {
+        *self.free.lock().unwrap()
+    }
+
+    /// Allocate the lowest free channel of `kind`
+    pub fn allocate(&self, kind: ChannelKind) -> Result<Channel<'_>> {
+        let mut free = self.free.lock().unwrap();
+        let channel = free
+            .iter()
+            .find(|&ch| self.soc.channel_kind(ch) == Some(kind))
+            .ok_or(Error::NoDmaChannel { kind })?;
+        free.remove(channel);
+        Ok(Channel {
+            allocator: self,
+            channel,
+            kind,
+        })
+    }
+
+    /// Allocate `channel`, e.g. one whose number is fixed by a device tree
+    pub fn allocate_channel(&self, channel: u32) -> Result<Channel<'_>> {
+        let mut free = self.free.lock().unwrap();
+        let kind = match self.soc.channel_kind(channel) {
+            Some(kind) if free.contains(channel) => kind,
+            _ => return Err(Error::DmaChannelUnavailable { channel }),
+        };
+        free.remove(channel);
+        Ok(Channel {
+            allocator: self,
+            channel,
+            kind,
+        })
+    }
+}
+
+/// Allocated DMA channel, released on drop
+#[derive(Debug)]
+pub struct Channel<'a> {
+    allocator: &'a Allocator,
+    channel: u32,
+    kind: ChannelKind,
+}
+
+impl<'a> Channel<'a> {
+    pub fn number(&self) -> u32 {
+        self.channel
+    }
+
+    pub fn kind(&self) -> ChannelKind {
+        self.kind
+    }
+}
+
+impl<'a> Drop for Channel<'a> {
+    fn drop(&mut self) {
+        self.allocator.free.lock().unwrap().insert(self.channel);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn classify_channels() {
+        assert_eq!(Soc::from_board_revision(0x000e), Some(Soc::Bcm283x));
+        assert_eq!(Soc::from_board_revision(0xa02082), Some(Soc::Bcm283x));
+        assert_eq!(Soc::from_board_revision(0xc03111), Some(Soc::Bcm2711));
+        assert_eq!(Soc::from_board_revision(0xd04170), None);
+
+        assert_eq!(Soc::Bcm283x.channels(ChannelKind::Lite).mask(), 0x7f80);
+        assert!(Soc::Bcm283x.channels(ChannelKind::Dma4).is_empty());
+        assert_eq!(Soc::Bcm2711.channels(ChannelKind::Lite).mask(), 0x0780);
+        assert_eq!(Soc::Bcm2711.channels(ChannelKind::Dma4).mask(), 0x7800);
+        assert_eq!(Soc::Bcm2711.channel_kind(15), None);
+    }
+
+    #[test]
+    fn allocate_channels() {
+        // Typical mask of a Raspberry Pi 4
+        let allocator = Allocator::new(Soc::Bcm2711, ChannelSet::from_mask(0x71f5));
+        assert_eq!(
+            allocator.available().to_string(),
+            "{0, 2, 4, 5, 6, 7, 8, 12, 13, 14}"
+        );
+
+        let a = allocator.allocate(ChannelKind::Normal).unwrap();
+        assert_eq!(a.number(), 0);
+        let b = allocator.allocate(ChannelKind::Dma4).unwrap();
+        assert_eq!(b.number(), 12);
+        assert!(matches!(
+            allocator.allocate_channel(12),
+            Err(Error::DmaChannelUnavailable { channel: 12 })
+        ));
+        assert!(matches!(
+            allocator.allocate_channel(1),
+            Err(Error::DmaChannelUnavailable { channel: 1 })
+        ));
+        let c = allocator.allocate_channel(7).unwrap();
+        assert_eq!(c.kind(), ChannelKind::Lite);
+        let d = allocator.allocate(ChannelKind::Lite).unwrap();
+        assert_eq!(d.number(), 8);
+        assert!(matches!(
+            allocator.allocate(ChannelKind::Lite),
+            Err(Error::NoDmaChannel {
+                kind: ChannelKind::Lite
+            })
+        ));
+
+        drop(c);
+        assert_eq!(allocator.allocate(ChannelKind::Lite).unwrap().number(), 7);
+        drop((a, b, d));
+        assert_eq!(allocator.free(), allocator.available());
+    }
+}
@@ -0,0 +1,180 @@
+//! Power domains of the newer firmware
+//!
+//! These domains are controlled separately from the devices of the legacy
+//! power state tag.
+//!
+
+use std::mem::size_of;
+
+use log::*;
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Power domains
+///
+/// Discriminants are the indices of `dt-bindings/power/raspberrypi-power.h`;
+/// the firmware numbers domains from 1, see `firmware_id`.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PowerDomain {
+    I2c0 = 0,
+    I2c1 = 1,
+    I2c2 = 2,
+    VideoScaler = 3,
+    Vpu1 = 4,
+    Hdmi = 5,
+    Usb = 6,
+    Vec = 7,
+    Jpeg = 8,
+    H264 = 9,
+    V3d = 10,
+    Isp = 11,
+    Unicam0 = 12,
+    Unicam1 = 13,
+    Ccp2rx = 14,
+    Csi2 = 15,
+    Cpi = 16,
+    Dsi0 = 17,
+    Dsi1 = 18,
+    Transposer = 19,
+    Ccp2tx = 20,
+    Cdp = 21,
+    Arm = 22,
+}
+
+impl PowerDomain {
+    /// Id of the domain for the firmware, the binding index plus one
+    pub fn firmware_id(self) -> u32 {
+        self as u32 + 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DomainState {
+    Off,
+    On,
+}
+
+impl DomainState {
+    fn from_u32(state: u32) -> Self {
+        match state {
+            0 => DomainState::Off,
+            _ => DomainState::On,
+        }
+    }
+}
+
+pub fn get_domain_state(mb: &Mailbox, domain: PowerDomain) -> Result<DomainState> {
+    use message::domain_state::*;
+
+    let mut msg = Message {
+        in_: In {
+            domain: domain.firmware_id(),
+            state: 0,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_DOMAIN_STATE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(DomainState::from_u32(msg.out.state)) }
+}
+
+/// Switch `domain` to `state`
+///
+/// Returns `Error::DomainStateMismatch` if the firmware reports another state.
+pub fn set_domain_state(mb: &Mailbox, domain: PowerDomain, state: DomainState) -> Result<()> {
+    use message::domain_state::*;
+
+    let mut msg = Message {
+        in_: In {
+            domain: domain.firmware_id(),
+            state: (state == DomainState::On) as u32,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_SET_DOMAIN_STATE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    if DomainState::from_u32(unsafe { msg.out.state }) != state {
+        return Err(Error::DomainStateMismatch {
+            domain,
+            requested: state,
+        });
+    }
+    Ok(())
+}
+
+/// Keeps a domain in a state while the guard lives
+///
+/// The prior state is restored when the guard is dropped.
+#[derive(Debug)]
+pub struct DomainGuard<'a> {
+    mb: &'a Mailbox,
+    domain: PowerDomain,
+    original: DomainState,
+}
+
+impl<'a> DomainGuard<'a> {
+    /// Switch `domain` to `state` remembering the current state
+    pub fn new(mb: &'a Mailbox, domain: PowerDomain, state: DomainState) -> Result<Self> {
+        let original = get_domain_state(mb, domain)?;
+        let guard = DomainGuard {
+            mb,
+            domain,
+            original,
+        };
+        if original != state {
+            set_domain_state(mb, domain, state)?;
+        }
+        Ok(guard)
+    }
+
+    /// Power on `domain` for the lifetime of the guard
+    pub fn enable(mb: &'a Mailbox, domain: PowerDomain) -> Result<Self> {
+        DomainGuard::new(mb, domain, DomainState::On)
+    }
+
+    pub fn domain(&self) -> PowerDomain {
+        self.domain
+    }
+
+    /// The state before the guard was created
+    pub fn original_state(&self) -> DomainState {
+        self.original
+    }
+}
+
+impl<'a> Drop for DomainGuard<'a> {
+    fn drop(&mut self) {
+        if let Err(err) = set_domain_state(self.mb, self.domain, self.original) {
+            error!(
+                "failed to restore domain {:?} to {:?}: {}",
+                self.domain, self.original, err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn firmware_ids() {
+        assert_eq!(PowerDomain::I2c0.firmware_id(), 1);
+        assert_eq!(PowerDomain::H264.firmware_id(), 10);
+        assert_eq!(PowerDomain::V3d.firmware_id(), 11);
+        assert_eq!(PowerDomain::Arm.firmware_id(), 23);
+    }
+}
@@ -0,0 +1,517 @@
+//! EDID of the attached monitor
+//!
+//! `read_edid` fetches the raw blocks through the firmware and
+//! `Edid::parse` decodes the base block and the CEA-861 extensions.
+//!
+
+use std::mem::size_of;
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// A 128 bytes EDID block
+pub type Block = [u8; 128];
+
+const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+
+/// Get the EDID block `block_num`
+///
+/// Returns `None` if the firmware reports that the block is not available.
+pub fn get_edid_block(mb: &Mailbox, block_num: u32) -> Result<Option<Block>> {
+    use message::edid_block::*;
+
+    let mut msg = Message {
+        in_: In { block_num },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_EDID_BLOCK,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe {
+        match msg.out.status {
+            0 => Ok(Some(msg.out.edid)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Read the base block and all the extension blocks
+///
+/// Blocks are read until the firmware reports failure.
+/// The checksum of each block is validated.
+pub fn read_edid(mb: &Mailbox) -> Result<Vec<Block>> {
+    read_blocks(|n| get_edid_block(mb, n))
+}
+
+/// Get the EDID block `block_num` of the display `display_num`
+///
+/// See `display::enumerate` for the display numbers.
+pub fn get_edid_block_display(
+    mb: &Mailbox,
+    block_num: u32,
+    display_num: u32,
+) -> Result<Option<Block>> {
+    use message::edid_block_display::*;
+
+    let mut msg = Message {
+        in_: In {
+            block_num,
+            display_num,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_EDID_BLOCK_DISPLAY,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe {
+        match msg.out.status {
+            0 => Ok(Some(msg.out.edid)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// `read_edid` for the display `display_num`
+pub fn read_edid_display(mb: &Mailbox, display_num: u32) -> Result<Vec<Block>> {
+    read_blocks(|n| get_edid_block_display(mb, n, display_num))
+}
+
+fn read_blocks<F>(mut get: F) -> Result<Vec<Block>>
+where
+    F: FnMut(u32) -> Result<Option<Block>>,
+{
+    let mut blocks = Vec::new();
+    // the extension count is a byte, so there are at most 256 blocks
+    for n in 0..256 {
+        let block = match get(n)? {
+            Some(block) => block,
+            None => break,
+        };
+        validate_checksum(n, &block)?;
+        blocks.push(block);
+    }
+    if blocks.is_empty() {
+        return Err(Error::InvalidEdid);
+    }
+    Ok(blocks)
+}
+
+/// Check that the bytes of `block` sum to zero
+pub fn validate_checksum(block_num: u32, block: &Block) -> Result<()> {
+    match block.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) {
+        0 => Ok(()),
+        _ => Err(Error::EdidChecksum { block_num }),
+    }
+}
+
+/// Detailed timing descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetailedTiming {
+    pub pixel_clock_khz: u32,
+    pub hactive: u32,
+    pub hblank: u32,
+    pub vactive: u32,
+    pub vblank: u32,
+    pub hsync_offset: u32,
+    pub hsync_width: u32,
+    pub vsync_offset: u32,
+    pub vsync_width: u32,
+    /// Image size in millimeters
+    pub width_mm: u32,
+    pub height_mm: u32,
+    pub interlaced: bool,
+}
+
+impl DetailedTiming {
+    fn parse(d: &[u8]) -> Option<Self> {
+        let pixel_clock = u16::from_le_bytes([d[0], d[1]]) as u32;
+        if pixel_clock == 0 {
+            return None;
+        }
+        let d: Vec<u32> = d.iter().map(|&b| b as u32).collect();
+        Some(DetailedTiming {
+            pixel_clock_khz: pixel_clock * 10,
+            hactive: d[2] | (d[4] >> 4) << 8,
+            hblank: d[3] | (d[4] & 0xf) << 8,
+            vactive: d[5] | (d[7] >> 4) << 8,
+            vblank: d[6] | (d[7] & 0xf) << 8,
+            hsync_offset: d[8] | (d[11] >> 6 & 3) << 8,
+            hsync_width: d[9] | (d[11] >> 4 & 3) << 8,
+            vsync_offset: d[10] >> 4 | (d[11] >> 2 & 3) << 4,
+            vsync_width: d[10] & 0xf | (d[11] & 3) << 4,
+            width_mm: d[12] | (d[14] >> 4) << 8,
+            height_mm: d[13] | (d[14] & 0xf) << 8,
+            interlaced: d[17] & 0x80 != 0,
+        })
+    }
+
+    /// Vertical refresh rate in Hz
+    pub fn refresh_rate(&self) -> f64 {
+        let total = (self.hactive + self.hblank) as f64 * (self.vactive + self.vblank) as f64;
+        if total == 0.0 {
+            return 0.0;
+        }
+        self.pixel_clock_khz as f64 * 1000.0 / total
+    }
+}
+
+/// Standard timing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StandardMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate: u32,
+}
+
+impl StandardMode {
+    fn parse(d: &[u8], version: (u8, u8)) -> Option<Self> {
+        if d[0] == 0 || (d[0] == 1 && d[1] == 1) {
+            return None;
+        }
+        let width = (d[0] as u32 + 31) * 8;
+        let height = match d[1] >> 6 {
+            0 if version < (1, 3) => width,
+            0 => width * 10 / 16,
+            1 => width * 3 / 4,
+            2 => width * 4 / 5,
+            _ => width * 9 / 16,
+        };
+        Some(StandardMode {
+            width,
+            height,
+            refresh_rate: (d[1] & 0x3f) as u32 + 60,
+        })
+    }
+}
+
+/// Short audio descriptor of a CEA extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioDescriptor {
+    /// Audio format code, e.g. 1 for LPCM
+    pub format: u8,
+    pub max_channels: u8,
+    /// Bit 0: 32kHz to bit 6: 192kHz
+    pub sample_rates: u8,
+    /// Bit depths for LPCM or maximum bit rate / 8kHz for compressed formats
+    pub detail: u8,
+}
+
+/// Short video descriptor of a CEA extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoDescriptor {
+    /// CEA-861 video identification code
+    pub vic: u8,
+    pub native: bool,
+}
+
+/// CEA-861 extension block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CeaExtension {
+    pub revision: u8,
+    pub underscan: bool,
+    pub basic_audio: bool,
+    pub ycbcr444: bool,
+    pub ycbcr422: bool,
+    pub native_formats: u8,
+    pub audio: Vec<AudioDescriptor>,
+    pub video: Vec<VideoDescriptor>,
+    /// Speaker allocation data block
+    pub speaker_allocation: Option<u8>,
+    pub detailed_timings: Vec<DetailedTiming>,
+}
+
+impl CeaExtension {
+    fn parse(block: &Block) -> Self {
+        let dtd_offset = (block[2] as usize).clamp(4, 127);
+        let mut audio = Vec::new();
+        let mut video = Vec::new();
+        let mut speaker_allocation = None;
+
+        let mut i = 4;
+        while i < dtd_offset {
+            let tag = block[i] >> 5;
+            let len = (block[i] & 0x1f) as usize;
+            let end = (i + 1 + len).min(dtd_offset);
+            let data = &block[i + 1..end];
+            match tag {
+                1 => audio.extend(data.chunks_exact(3).map(|d| AudioDescriptor {
+                    format: d[0] >> 3 & 0xf,
+                    max_channels: (d[0] & 7) + 1,
+                    sample_rates: d[1] & 0x7f,
+                    detail: d[2],
+                })),
+                2 => video.extend(data.iter().map(|&b| match b {
+                    129..=192 => VideoDescriptor {
+                        vic: b & 0x7f,
+                        native: true,
+                    },
+                    _ => VideoDescriptor {
+                        vic: b,
+                        native: false,
+                    },
+                })),
+                4 => speaker_allocation = data.first().copied(),
+                _ => {}
+            }
+            i = end;
+        }
+
+        let detailed_timings = if block[2] == 0 {
+            Vec::new()
+        } else {
+            block[dtd_offset..127]
+                .chunks_exact(18)
+                .map_while(DetailedTiming::parse)
+                .collect()
+        };
+
+        CeaExtension {
+            revision: block[1],
+            underscan: block[3] & 0x80 != 0,
+            basic_audio: block[3] & 0x40 != 0,
+            ycbcr444: block[3] & 0x20 != 0,
+            ycbcr422: block[3] & 0x10 != 0,
+            native_formats: block[3] & 0xf,
+            audio,
+            video,
+            speaker_allocation,
+            detailed_timings,
+        }
+    }
+}
+
+/// Parsed EDID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edid {
+    /// Three letters PNP id
+    pub manufacturer: String,
+    pub product: u16,
+    pub serial: u32,
+    /// Serial number descriptor
+    pub serial_string: Option<String>,
+    /// Monitor name descriptor
+    pub name: Option<String>,
+    pub week: u8,
+    pub year: u16,
+    pub version: (u8, u8),
+    /// Maximum image size in centimeters
+    pub physical_size: (u32, u32),
+    pub preferred_timing: Option<DetailedTiming>,
+    pub detailed_timings: Vec<DetailedTiming>,
+    pub standard_modes: Vec<StandardMode>,
+    pub cea_extensions: Vec<CeaExtension>,
+}
+
+impl Edid {
+    /// Parse the blocks returned by `read_edid`
+    pub fn parse(blocks: &[Block]) -> Result<Self> {
+        let base = blocks.first().ok_or(Error::InvalidEdid)?;
+        if base[..8] != HEADER {
+            return Err(Error::InvalidEdid);
+        }
+
+        let id = u16::from_be_bytes([base[8], base[9]]);
+        let manufacturer = [10, 5, 0]
+            .iter()
+            .map(|shift| (b'A' - 1 + (id >> shift & 0x1f) as u8) as char)
+            .collect();
+        let version = (base[18], base[19]);
+
+        let mut detailed_timings = Vec::new();
+        let mut serial_string = None;
+        let mut name = None;
+        for d in base[54..126].chunks_exact(18) {
+            if let Some(timing) = DetailedTiming::parse(d) {
+                detailed_timings.push(timing);
+                continue;
+            }
+            match d[3] {
+                0xff => serial_string = Some(descriptor_text(d)),
+                0xfc => name = Some(descriptor_text(d)),
+                _ => {}
+            }
+        }
+
+        let cea_extensions = blocks[1..]
+            .iter()
+            .filter(|b| b[0] == 0x02)
+            .map(CeaExtension::parse)
+            .collect();
+
+        Ok(Edid {
+            manufacturer,
+            product: u16::from_le_bytes([base[10], base[11]]),
+            serial: u32::from_le_bytes([base[12], base[13], base[14], base[15]]),
+            serial_string,
+            name,
+            week: base[16],
+            year: base[17] as u16 + 1990,
+            version,
+            physical_size: (base[21] as u32, base[22] as u32),
+            preferred_timing: detailed_timings.first().copied(),
+            detailed_timings,
+            standard_modes: base[38..54]
+                .chunks_exact(2)
+                .filter_map(|d| StandardMode::parse(d, version))
+                .collect(),
+            cea_extensions,
+        })
+    }
+}
+
+fn descriptor_text(d: &[u8]) -> String {
+    let text = &d[5..18];
+    let end = text.iter().position(|&b| b == 0x0a).unwrap_or(text.len());
+    String::from_utf8_lossy(&text[..end]).trim_end().to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn checksum(block: &mut Block) {
+        let sum = block[..127].iter().fold(0u8, |s, b| s.wrapping_add(*b));
+        block[127] = 0u8.wrapping_sub(sum);
+    }
+
+    // 1920x1080@60 (148.5MHz)
+    const DTD_1080P: [u8; 18] = [
+        0x02, 0x3a, 0x80, 0x18, 0x71, 0x38, 0x2d, 0x40, 0x58, 0x2c, 0x45, 0x00, 0x10, 0x29, 0x21,
+        0x00, 0x00, 0x1e,
+    ];
+
+    fn base_block() -> Block {
+        let mut b = [0u8; 128];
+        b[..8].copy_from_slice(&HEADER);
+        // "DEL"
+        b[8..10].copy_from_slice(&[0x10, 0xac]);
+        b[10..12].copy_from_slice(&0x4321u16.to_le_bytes());
+        b[12..16].copy_from_slice(&12345u32.to_le_bytes());
+        b[16] = 10;
+        b[17] = 30;
+        b[18] = 1;
+        b[19] = 3;
+        b[21] = 53;
+        b[22] = 30;
+        b[38..54].copy_from_slice(&[
+            0xd1, 0xc0, 0x81, 0x80, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01,
+            0x01, 0x01,
+        ]);
+        b[54..72].copy_from_slice(&DTD_1080P);
+        b[72..90].copy_from_slice(&[
+            0, 0, 0, 0xfc, 0, b'M', b'o', b'n', b'i', b't', b'o', b'r', 0x0a, 0x20, 0x20, 0x20,
+            0x20, 0x20,
+        ]);
+        b[126] = 1;
+        checksum(&mut b);
+        b
+    }
+
+    fn cea_block() -> Block {
+        let mut b = [0u8; 128];
+        b[0] = 0x02;
+        b[1] = 3;
+        b[3] = 0x71;
+        #[rustfmt::skip]
+        let dbc = [
+            // audio: LPCM 2ch 32/44.1/48kHz 16/20/24bit
+            0x23, 0x09, 0x07, 0x07,
+            // video: VIC 16 native, VIC 4
+            0x42, 0x90, 0x04,
+            // speaker allocation: FL/FR
+            0x83, 0x01, 0x00, 0x00,
+        ];
+        b[4..4 + dbc.len()].copy_from_slice(&dbc);
+        b[2] = (4 + dbc.len()) as u8;
+        b[4 + dbc.len()..4 + dbc.len() + 18].copy_from_slice(&DTD_1080P);
+        checksum(&mut b);
+        b
+    }
+
+    #[test]
+    fn read_until_failure() {
+        let blocks = [base_block(), cea_block()];
+        let read = read_blocks(|n| Ok(blocks.get(n as usize).copied())).unwrap();
+        assert_eq!(read.len(), 2);
+
+        let mut broken = base_block();
+        broken[20] ^= 1;
+        assert!(matches!(
+            read_blocks(|n| Ok((n == 0).then_some(broken))),
+            Err(Error::EdidChecksum { block_num: 0 })
+        ));
+    }
+
+    #[test]
+    fn parse_base_block() {
+        let edid = Edid::parse(&[base_block()]).unwrap();
+        assert_eq!(edid.manufacturer, "DEL");
+        assert_eq!(edid.product, 0x4321);
+        assert_eq!(edid.serial, 12345);
+        assert_eq!(edid.year, 2020);
+        assert_eq!(edid.name.as_deref(), Some("Monitor"));
+        assert_eq!(edid.physical_size, (53, 30));
+
+        let preferred = edid.preferred_timing.unwrap();
+        assert_eq!((preferred.hactive, preferred.vactive), (1920, 1080));
+        assert_eq!(preferred.pixel_clock_khz, 148_500);
+        assert_eq!(preferred.refresh_rate().round(), 60.0);
+        assert_eq!((preferred.width_mm, preferred.height_mm), (528, 297));
+
+        assert_eq!(
+            edid.standard_modes,
+            vec![
+                StandardMode {
+                    width: 1920,
+                    height: 1080,
+                    refresh_rate: 60
+                },
+                StandardMode {
+                    width: 1280,
+                    height: 1024,
+                    refresh_rate: 60
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_cea_extension() {
+        let edid = Edid::parse(&[base_block(), cea_block()]).unwrap();
+        let cea = &edid.cea_extensions[0];
+        assert!(cea.basic_audio && cea.ycbcr444 && cea.ycbcr422 && !cea.underscan);
+        assert_eq!(
+            cea.audio,
+            vec![AudioDescriptor {
+                format: 1,
+                max_channels: 2,
+                sample_rates: 0x07,
+                detail: 0x07,
+            }]
+        );
+        assert_eq!(
+            cea.video,
+            vec![
+                VideoDescriptor {
+                    vic: 16,
+                    native: true
+                },
+                VideoDescriptor {
+                    vic: 4,
+                    native: false
+                },
+            ]
+        );
+        assert_eq!(cea.speaker_allocation, Some(0x01));
+        assert_eq!(cea.detailed_timings.len(), 1);
+    }
+}
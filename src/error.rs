@@ -22,4 +22,104 @@ pub enum Error {
         req_resp_size: usize,
         supplied: usize,
     },
+    #[error(
+        "clock {} rate mismatch: requested {} but firmware set {}",
+        clock_id,
+        requested,
+        actual
+    )]
+    ClockRateMismatch {
+        clock_id: u32,
+        requested: u32,
+        actual: u32,
+    },
+    #[error("turbo mode was not set to {}", requested)]
+    TurboMismatch { requested: bool },
+    #[error("framebuffer allocation failed")]
+    FramebufferAllocationFailed,
+    #[error(
+        "virtual offset mismatch: requested {} but firmware set {}",
+        requested,
+        actual
+    )]
+    VirtualOffsetMismatch { requested: u32, actual: u32 },
+    #[error("invalid palette: {} entries at {}", length, offset)]
+    InvalidPalette { offset: u32, length: u32 },
+    #[error("cursor rejected by firmware: {}", status)]
+    CursorRejected { status: u32 },
+    #[error("invalid cursor image for {}x{}", width, height)]
+    InvalidCursorImage { width: u32, height: u32 },
+    #[error("unsupported depth: {}", depth)]
+    UnsupportedDepth { depth: u32 },
+    #[error("buffer too small: {} < {}", size, required)]
+    BufferTooSmall { size: usize, required: usize },
+    #[error("invalid EDID")]
+    InvalidEdid,
+    #[error("EDID block {} checksum error", block_num)]
+    EdidChecksum { block_num: u32 },
+    #[error("no free DMA channel of kind {:?}", kind)]
+    NoDmaChannel { kind: crate::dma::ChannelKind },
+    #[error("DMA channel {} is not available", channel)]
+    DmaChannelUnavailable { channel: u32 },
+    #[error("GPIO {} rejected by firmware: {}", gpio, status)]
+    GpioRejected { gpio: u32, status: u32 },
+    #[error("write to peripheral register {:#x} is not allowed", reg)]
+    PeriphWriteNotAllowed { reg: u32 },
+    #[error("invalid OTP range: {} rows from {}", count, start)]
+    InvalidOtpRange { start: u32, count: u32 },
+    #[error(
+        "OTP row {} would clear programmed bits: {:#010x} -> {:#010x}",
+        row,
+        current,
+        requested
+    )]
+    OtpBitsCleared {
+        row: u32,
+        current: u32,
+        requested: u32,
+    },
+    #[error(
+        "OTP row {} verify failed: expected {:#010x} but read {:#010x}",
+        row,
+        expected,
+        actual
+    )]
+    OtpVerifyFailed {
+        row: u32,
+        expected: u32,
+        actual: u32,
+    },
+    #[error("private key is already programmed")]
+    PrivateKeyProgrammed,
+    #[error("private key verify failed")]
+    PrivateKeyVerifyFailed,
+    #[error("domain {:?} was not set to {:?}", domain, requested)]
+    DomainStateMismatch {
+        domain: crate::domain::PowerDomain,
+        requested: crate::domain::DomainState,
+    },
+    #[error("invalid VPU code of {} bytes", size)]
+    InvalidVpuCode { size: usize },
+    #[error("QPU enable rejected by firmware: {}", status)]
+    QpuEnableFailed { status: u32 },
+    #[error("QPU jobs did not complete before the timeout: {}", status)]
+    QpuTimeout { status: u32 },
+    #[error("invalid number of QPU jobs: {}", count)]
+    InvalidQpuJobs { count: usize },
+    #[error("QPU job {} has no code", job)]
+    EmptyQpuJob { job: usize },
+    #[error("QPU jobs do not fit in the VideoCore address space")]
+    QpuJobsTooLarge,
+    #[error("unknown dispmanx resource {}: {}", resource, status)]
+    UnknownResource { resource: u32, status: u32 },
+    #[error("VCHIQ initialisation failed: {}", status)]
+    VchiqInitFailed { status: u32 },
+    #[error("invalid number of VCHIQ slots: {}", num_slots)]
+    InvalidSlotCount { num_slots: u32 },
+    #[error("invalid number of framebuffer pages: {}", pages)]
+    InvalidPageCount { pages: u32 },
+    #[error("invalid GPIO expander pin: {}", pin)]
+    InvalidGpioPin { pin: u32 },
+    #[error("unsupported SoC in board revision {:#x}", revision)]
+    UnsupportedSoc { revision: u32 },
 }
@@ -0,0 +1,1090 @@
+//! Framebuffer allocated by the firmware
+//!
+//! `FramebufferBuilder` sets up the whole mode and allocates the buffer in
+//! a single property list, so the firmware never sees a half-configured mode.
+//!
+
+use std::mem::size_of;
+
+use log::*;
+
+use crate::display;
+use crate::error::{Error, Result};
+use crate::kernel::{rpi_firmware_property, rpi_firmware_property_batch, PropertyTag};
+use crate::mailbox::Mailbox;
+use crate::message;
+use crate::mmap::MemoryMap;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::{self, *};
+
+/// Order of the color components of a pixel
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelOrder {
+    Bgr = 0,
+    Rgb = 1,
+}
+
+impl PixelOrder {
+    pub fn from_u32(state: u32) -> Option<Self> {
+        match state {
+            0 => Some(PixelOrder::Bgr),
+            1 => Some(PixelOrder::Rgb),
+            _ => None,
+        }
+    }
+}
+
+/// Interpretation of the alpha channel
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    /// 0 is fully opaque
+    Enabled = 0,
+    /// 0 is fully transparent
+    Reversed = 1,
+    Ignored = 2,
+}
+
+impl AlphaMode {
+    pub fn from_u32(state: u32) -> Option<Self> {
+        match state {
+            0 => Some(AlphaMode::Enabled),
+            1 => Some(AlphaMode::Reversed),
+            2 => Some(AlphaMode::Ignored),
+            _ => None,
+        }
+    }
+}
+
+/// Overscan in pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Overscan {
+    pub top: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub right: u32,
+}
+
+fn set_or_get(
+    set: bool,
+    set_tag: rpi_firmware_property_tag,
+    get_tag: rpi_firmware_property_tag,
+) -> rpi_firmware_property_tag {
+    if set {
+        set_tag
+    } else {
+        get_tag
+    }
+}
+
+/// Mode of a framebuffer
+///
+/// `None` leaves the current setting of the firmware as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramebufferBuilder {
+    physical: (u32, u32),
+    virtual_: Option<(u32, u32)>,
+    depth: u32,
+    pixel_order: Option<PixelOrder>,
+    alpha_mode: Option<AlphaMode>,
+    virtual_offset: Option<(u32, u32)>,
+    overscan: Option<Overscan>,
+    alignment: u32,
+    display: Option<u32>,
+}
+
+impl FramebufferBuilder {
+    /// `width` x `height` pixels of 32 bits depth
+    ///
+    /// The virtual size defaults to the physical size.
+    pub fn new(width: u32, height: u32) -> Self {
+        FramebufferBuilder {
+            physical: (width, height),
+            virtual_: None,
+            depth: 32,
+            pixel_order: None,
+            alpha_mode: None,
+            virtual_offset: None,
+            overscan: None,
+            alignment: 4096,
+            display: None,
+        }
+    }
+
+    pub fn virtual_size(mut self, width: u32, height: u32) -> Self {
+        self.virtual_ = Some((width, height));
+        self
+    }
+
+    /// Bits per pixel
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn pixel_order(mut self, pixel_order: PixelOrder) -> Self {
+        self.pixel_order = Some(pixel_order);
+        self
+    }
+
+    pub fn alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.alpha_mode = Some(alpha_mode);
+        self
+    }
+
+    pub fn virtual_offset(mut self, x: u32, y: u32) -> Self {
+        self.virtual_offset = Some((x, y));
+        self
+    }
+
+    pub fn overscan(mut self, overscan: Overscan) -> Self {
+        self.overscan = Some(overscan);
+        self
+    }
+
+    /// Alignment of the buffer in bytes
+    pub fn alignment(mut self, alignment: u32) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Target the display `display_num` instead of the current one
+    ///
+    /// See `display::enumerate` for the display numbers.
+    /// The selection is global to the firmware: `allocate` and the later calls on
+    /// the framebuffer select `display_num` and leave it selected, as the firmware
+    /// cannot report the previous selection to restore.
+    pub fn display(mut self, display_num: u32) -> Self {
+        self.display = Some(display_num);
+        self
+    }
+
+    /// Ask the firmware which mode it would accept for this configuration
+    ///
+    /// Each parameter set on the builder is submitted through its TEST tag
+    /// in one property list. Nothing is applied or allocated.
+    ///
+    /// With `display`, the display is selected first and stays selected, as in
+    /// `allocate`, so that the mode is tested against the display it is meant for.
+    pub fn negotiate(&self, mb: &Mailbox) -> Result<Negotiation> {
+        use message::*;
+
+        let (width, height) = self.physical;
+        let (vwidth, vheight) = self.virtual_.unwrap_or(self.physical);
+        let (xoffset, yoffset) = self.virtual_offset.unwrap_or((0, 0));
+        let overscan = self.overscan.unwrap_or_default();
+        let mut display = fb_set_display_num::Message {
+            in_: fb_set_display_num::In {
+                display_num: self.display.unwrap_or(0),
+            },
+        };
+
+        let mut physical = fb_physical_width_height::Message {
+            in_: fb_physical_width_height::In { width, height },
+        };
+        let mut virtual_ = fb_virtual_width_height::Message {
+            in_: fb_virtual_width_height::In {
+                width: vwidth,
+                height: vheight,
+            },
+        };
+        let mut depth = fb_depth::Message {
+            in_: fb_depth::In { depth: self.depth },
+        };
+        let mut pixel_order = fb_pixel_order::Message {
+            in_: fb_pixel_order::In {
+                state: self.pixel_order.map_or(0, |p| p as u32),
+            },
+        };
+        let mut alpha_mode = fb_alpha_mode::Message {
+            in_: fb_alpha_mode::In {
+                state: self.alpha_mode.map_or(0, |a| a as u32),
+            },
+        };
+        let mut virtual_offset = fb_virtual_offset::Message {
+            in_: fb_virtual_offset::In {
+                x: xoffset,
+                y: yoffset,
+            },
+        };
+        let mut overscan = fb_overscan::Message {
+            in_: fb_overscan::In {
+                top: overscan.top,
+                bottom: overscan.bottom,
+                left: overscan.left,
+                right: overscan.right,
+            },
+        };
+
+        {
+            let mut tags = unsafe {
+                vec![
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_TEST_PHYSICAL_WIDTH_HEIGHT,
+                        &mut physical,
+                        size_of::<fb_physical_width_height::Out>(),
+                    ),
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_TEST_VIRTUAL_WIDTH_HEIGHT,
+                        &mut virtual_,
+                        size_of::<fb_virtual_width_height::Out>(),
+                    ),
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_TEST_DEPTH,
+                        &mut depth,
+                        size_of::<fb_depth::Out>(),
+                    ),
+                ]
+            };
+            if self.pixel_order.is_some() {
+                tags.push(unsafe {
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_TEST_PIXEL_ORDER,
+                        &mut pixel_order,
+                        size_of::<fb_pixel_order::Out>(),
+                    )
+                });
+            }
+            if self.alpha_mode.is_some() {
+                tags.push(unsafe {
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_TEST_ALPHA_MODE,
+                        &mut alpha_mode,
+                        size_of::<fb_alpha_mode::Out>(),
+                    )
+                });
+            }
+            if self.virtual_offset.is_some() {
+                tags.push(unsafe {
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_TEST_VIRTUAL_OFFSET,
+                        &mut virtual_offset,
+                        size_of::<fb_virtual_offset::Out>(),
+                    )
+                });
+            }
+            if self.overscan.is_some() {
+                tags.push(unsafe {
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_TEST_OVERSCAN,
+                        &mut overscan,
+                        size_of::<fb_overscan::Out>(),
+                    )
+                });
+            }
+            if self.display.is_some() {
+                tags.insert(0, unsafe {
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_SET_DISPLAY_NUM,
+                        &mut display,
+                        size_of::<fb_set_display_num::Out>(),
+                    )
+                });
+            }
+            rpi_firmware_property_batch(mb, &mut tags)?;
+        }
+
+        let mut closest = *self;
+        unsafe {
+            closest.physical = (physical.out.width, physical.out.height);
+            closest.virtual_ = Some((virtual_.out.width, virtual_.out.height));
+            closest.depth = depth.out.depth;
+            if self.pixel_order.is_some() {
+                closest.pixel_order = PixelOrder::from_u32(pixel_order.out.state);
+            }
+            if self.alpha_mode.is_some() {
+                closest.alpha_mode = AlphaMode::from_u32(alpha_mode.out.state);
+            }
+            if self.virtual_offset.is_some() {
+                closest.virtual_offset = Some((virtual_offset.out.x, virtual_offset.out.y));
+            }
+            if self.overscan.is_some() {
+                closest.overscan = Some(Overscan {
+                    top: overscan.out.top,
+                    bottom: overscan.out.bottom,
+                    left: overscan.out.left,
+                    right: overscan.out.right,
+                });
+            }
+        }
+
+        let requested = FramebufferBuilder {
+            virtual_: Some((vwidth, vheight)),
+            ..*self
+        };
+        let adjusted = [
+            (
+                Parameter::PhysicalSize,
+                requested.physical != closest.physical,
+            ),
+            (
+                Parameter::VirtualSize,
+                requested.virtual_ != closest.virtual_,
+            ),
+            (Parameter::Depth, requested.depth != closest.depth),
+            (
+                Parameter::PixelOrder,
+                requested.pixel_order != closest.pixel_order,
+            ),
+            (
+                Parameter::AlphaMode,
+                requested.alpha_mode != closest.alpha_mode,
+            ),
+            (
+                Parameter::VirtualOffset,
+                requested.virtual_offset != closest.virtual_offset,
+            ),
+            (Parameter::Overscan, requested.overscan != closest.overscan),
+        ]
+        .iter()
+        .filter(|(_, adjusted)| *adjusted)
+        .map(|&(p, _)| p)
+        .collect();
+
+        Ok(Negotiation {
+            requested,
+            closest,
+            adjusted,
+        })
+    }
+
+    /// Set the mode and allocate the buffer
+    ///
+    /// The buffer is released when the returned `Framebuffer` is dropped.
+    /// With `display`, the display is selected first and stays selected.
+    pub fn allocate<'a>(&self, mb: &'a Mailbox) -> Result<Framebuffer<'a>> {
+        use message::*;
+
+        let (width, height) = self.physical;
+        let (vwidth, vheight) = self.virtual_.unwrap_or(self.physical);
+        let (xoffset, yoffset) = self.virtual_offset.unwrap_or((0, 0));
+        let overscan = self.overscan.unwrap_or_default();
+        let mut display = fb_set_display_num::Message {
+            in_: fb_set_display_num::In {
+                display_num: self.display.unwrap_or(0),
+            },
+        };
+
+        let mut physical = fb_physical_width_height::Message {
+            in_: fb_physical_width_height::In { width, height },
+        };
+        let mut virtual_ = fb_virtual_width_height::Message {
+            in_: fb_virtual_width_height::In {
+                width: vwidth,
+                height: vheight,
+            },
+        };
+        let mut depth = fb_depth::Message {
+            in_: fb_depth::In { depth: self.depth },
+        };
+        let mut pixel_order = fb_pixel_order::Message {
+            in_: fb_pixel_order::In {
+                state: self.pixel_order.map_or(0, |p| p as u32),
+            },
+        };
+        let mut alpha_mode = fb_alpha_mode::Message {
+            in_: fb_alpha_mode::In {
+                state: self.alpha_mode.map_or(0, |a| a as u32),
+            },
+        };
+        let mut virtual_offset = fb_virtual_offset::Message {
+            in_: fb_virtual_offset::In {
+                x: xoffset,
+                y: yoffset,
+            },
+        };
+        let mut overscan = fb_overscan::Message {
+            in_: fb_overscan::In {
+                top: overscan.top,
+                bottom: overscan.bottom,
+                left: overscan.left,
+                right: overscan.right,
+            },
+        };
+
+        {
+            let mut tags = unsafe {
+                vec![
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_SET_PHYSICAL_WIDTH_HEIGHT,
+                        &mut physical,
+                        size_of::<fb_physical_width_height::Out>(),
+                    ),
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_SET_VIRTUAL_WIDTH_HEIGHT,
+                        &mut virtual_,
+                        size_of::<fb_virtual_width_height::Out>(),
+                    ),
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_SET_DEPTH,
+                        &mut depth,
+                        size_of::<fb_depth::Out>(),
+                    ),
+                    PropertyTag::new(
+                        set_or_get(
+                            self.pixel_order.is_some(),
+                            RPI_FIRMWARE_FRAMEBUFFER_SET_PIXEL_ORDER,
+                            RPI_FIRMWARE_FRAMEBUFFER_GET_PIXEL_ORDER,
+                        ),
+                        &mut pixel_order,
+                        size_of::<fb_pixel_order::Out>(),
+                    ),
+                    PropertyTag::new(
+                        set_or_get(
+                            self.alpha_mode.is_some(),
+                            RPI_FIRMWARE_FRAMEBUFFER_SET_ALPHA_MODE,
+                            RPI_FIRMWARE_FRAMEBUFFER_GET_ALPHA_MODE,
+                        ),
+                        &mut alpha_mode,
+                        size_of::<fb_alpha_mode::Out>(),
+                    ),
+                    PropertyTag::new(
+                        set_or_get(
+                            self.virtual_offset.is_some(),
+                            RPI_FIRMWARE_FRAMEBUFFER_SET_VIRTUAL_OFFSET,
+                            RPI_FIRMWARE_FRAMEBUFFER_GET_VIRTUAL_OFFSET,
+                        ),
+                        &mut virtual_offset,
+                        size_of::<fb_virtual_offset::Out>(),
+                    ),
+                    PropertyTag::new(
+                        set_or_get(
+                            self.overscan.is_some(),
+                            RPI_FIRMWARE_FRAMEBUFFER_SET_OVERSCAN,
+                            RPI_FIRMWARE_FRAMEBUFFER_GET_OVERSCAN,
+                        ),
+                        &mut overscan,
+                        size_of::<fb_overscan::Out>(),
+                    ),
+                ]
+            };
+            if self.display.is_some() {
+                tags.insert(0, unsafe {
+                    PropertyTag::new(
+                        RPI_FIRMWARE_FRAMEBUFFER_SET_DISPLAY_NUM,
+                        &mut display,
+                        size_of::<fb_set_display_num::Out>(),
+                    )
+                });
+            }
+            rpi_firmware_property_batch(mb, &mut tags)?;
+        }
+
+        // Allocate on its own once the mode is set, so that a failing mode
+        // tag cannot leave a buffer behind that nobody releases
+        let mut allocate = fb_allocate::Message {
+            in_: fb_allocate::In {
+                alignment: self.alignment,
+            },
+        };
+        rpi_firmware_property(
+            mb,
+            RPI_FIRMWARE_FRAMEBUFFER_ALLOCATE,
+            &mut allocate as *mut fb_allocate::Message as *mut u8,
+            size_of::<fb_allocate::Message>(),
+            size_of::<fb_allocate::Out>(),
+        )?;
+
+        let mut fb = unsafe {
+            if allocate.out.base == 0 {
+                return Err(Error::FramebufferAllocationFailed);
+            }
+            Framebuffer {
+                mb,
+                physical: (physical.out.width, physical.out.height),
+                virtual_: (virtual_.out.width, virtual_.out.height),
+                depth: depth.out.depth,
+                pixel_order: PixelOrder::from_u32(pixel_order.out.state).unwrap_or(PixelOrder::Bgr),
+                alpha_mode: AlphaMode::from_u32(alpha_mode.out.state).unwrap_or(AlphaMode::Enabled),
+                virtual_offset: (virtual_offset.out.x, virtual_offset.out.y),
+                overscan: Overscan {
+                    top: overscan.out.top,
+                    bottom: overscan.out.bottom,
+                    left: overscan.out.left,
+                    right: overscan.out.right,
+                },
+                pitch: 0,
+                bus_address: allocate.out.base,
+                size: allocate.out.size,
+                display: self.display,
+            }
+        };
+
+        // From here on, dropping `fb` on error releases the buffer
+        let mut pitch = fb_pitch::Message {
+            out: fb_pitch::Out { pitch: 0 },
+        };
+        rpi_firmware_property(
+            mb,
+            RPI_FIRMWARE_FRAMEBUFFER_GET_PITCH,
+            &mut pitch as *mut fb_pitch::Message as *mut u8,
+            size_of::<fb_pitch::Message>(),
+            size_of::<fb_pitch::Out>(),
+        )?;
+        fb.pitch = unsafe { pitch.out.pitch };
+        Ok(fb)
+    }
+
+    /// Allocate a framebuffer of `pages` pages stacked vertically for page flipping
+    ///
+    /// The virtual size is set to `pages` times the physical height and the
+    /// virtual offset to the first page.
+    /// Fails with `Error::InvalidPageCount` if `pages` is 0 or the virtual height overflows.
+    pub fn allocate_paged<'a>(&self, mb: &'a Mailbox, pages: u32) -> Result<PagedFramebuffer<'a>> {
+        let (width, height) = self.physical;
+        let virtual_height = match height.checked_mul(pages) {
+            Some(h) if pages != 0 => h,
+            _ => return Err(Error::InvalidPageCount { pages }),
+        };
+        let fb = self
+            .virtual_size(width, virtual_height)
+            .virtual_offset(0, 0)
+            .allocate(mb)?;
+        match fb.physical_size().1.checked_mul(pages) {
+            Some(required) if required <= fb.virtual_size().1 => {}
+            _ => return Err(Error::FramebufferAllocationFailed),
+        }
+        let vsync = test_vsync(mb)?;
+        Ok(PagedFramebuffer {
+            fb,
+            pages,
+            front: 0,
+            vsync,
+            map: None,
+            flips: 0,
+            vsyncs: 0,
+        })
+    }
+}
+
+/// A parameter of a framebuffer mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Parameter {
+    PhysicalSize,
+    VirtualSize,
+    Depth,
+    PixelOrder,
+    AlphaMode,
+    VirtualOffset,
+    Overscan,
+}
+
+/// Result of `FramebufferBuilder::negotiate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Negotiation {
+    requested: FramebufferBuilder,
+    closest: FramebufferBuilder,
+    adjusted: Vec<Parameter>,
+}
+
+impl Negotiation {
+    /// The submitted configuration
+    pub fn requested(&self) -> &FramebufferBuilder {
+        &self.requested
+    }
+
+    /// The closest mode the firmware supports, which can be passed to `allocate`
+    pub fn closest(&self) -> &FramebufferBuilder {
+        &self.closest
+    }
+
+    /// Parameters which the firmware changed
+    pub fn adjusted(&self) -> &[Parameter] {
+        &self.adjusted
+    }
+
+    /// Whether the firmware accepts the configuration as is
+    pub fn is_exact(&self) -> bool {
+        self.adjusted.is_empty()
+    }
+}
+
+/// Framebuffer allocated by `FramebufferBuilder::allocate`
+///
+/// The values are the ones reported back by the firmware, which may differ
+/// from the requested ones.
+/// The buffer is released on drop, after selecting its display if it has one.
+#[derive(Debug)]
+pub struct Framebuffer<'a> {
+    mb: &'a Mailbox,
+    physical: (u32, u32),
+    virtual_: (u32, u32),
+    depth: u32,
+    pixel_order: PixelOrder,
+    alpha_mode: AlphaMode,
+    virtual_offset: (u32, u32),
+    overscan: Overscan,
+    pitch: u32,
+    bus_address: u32,
+    size: u32,
+    display: Option<u32>,
+}
+
+impl<'a> Framebuffer<'a> {
+    pub fn mailbox(&self) -> &'a Mailbox {
+        self.mb
+    }
+
+    /// Physical (display) width and height
+    pub fn physical_size(&self) -> (u32, u32) {
+        self.physical
+    }
+
+    /// Virtual (buffer) width and height
+    pub fn virtual_size(&self) -> (u32, u32) {
+        self.virtual_
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn pixel_order(&self) -> PixelOrder {
+        self.pixel_order
+    }
+
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    pub fn virtual_offset(&self) -> (u32, u32) {
+        self.virtual_offset
+    }
+
+    pub fn overscan(&self) -> Overscan {
+        self.overscan
+    }
+
+    /// Bytes per line
+    pub fn pitch(&self) -> u32 {
+        self.pitch
+    }
+
+    /// Bus address of the buffer as seen from the VideoCore
+    pub fn bus_address(&self) -> u32 {
+        self.bus_address
+    }
+
+    /// Size of the buffer in bytes
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The display given to `FramebufferBuilder::display`
+    pub fn display(&self) -> Option<u32> {
+        self.display
+    }
+
+    /// Direct the following framebuffer tags to the display of this framebuffer
+    ///
+    /// The display stays selected for every framebuffer request on the system.
+    pub(crate) fn select_display(&self) -> Result<()> {
+        if let Some(display_num) = self.display {
+            display::set_display_num(self.mb, display_num)?;
+        }
+        Ok(())
+    }
+
+    /// Map the buffer into the process
+    pub fn map(&self) -> Result<MemoryMap> {
+        MemoryMap::new(self.bus_address, self.size as usize)
+    }
+}
+
+impl<'a> Drop for Framebuffer<'a> {
+    fn drop(&mut self) {
+        use message::fb_release::*;
+
+        let mut msg = Message { in_: In };
+        let res = self.select_display().and_then(|()| {
+            rpi_firmware_property(
+                self.mb,
+                RPI_FIRMWARE_FRAMEBUFFER_RELEASE,
+                &mut msg as *mut Message as *mut u8,
+                size_of::<Message>(),
+                size_of::<Out>(),
+            )
+        });
+        if let Err(err) = res {
+            error!("failed to release framebuffer: {}", err);
+        }
+    }
+}
+
+/// Set the virtual offset of the current framebuffer
+///
+/// Returns the offset applied by the firmware.
+pub fn set_virtual_offset(mb: &Mailbox, x: u32, y: u32) -> Result<(u32, u32)> {
+    use message::fb_virtual_offset::*;
+
+    let mut msg = Message { in_: In { x, y } };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_SET_VIRTUAL_OFFSET,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok((msg.out.x, msg.out.y)) }
+}
+
+/// Wait for the next vertical sync of the display
+pub fn wait_for_vsync(mb: &Mailbox) -> Result<()> {
+    use message::fb_vsync::*;
+
+    let mut msg = Message {
+        in_: In { dummy: 0 },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_SET_VSYNC,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )
+}
+
+/// Whether the firmware supports waiting for vertical sync
+pub fn test_vsync(mb: &Mailbox) -> Result<bool> {
+    use message::fb_vsync::*;
+
+    let mut msg = Message {
+        in_: In { dummy: 0 },
+    };
+    match rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_TEST_VSYNC,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    ) {
+        Ok(()) => Ok(true),
+        Err(Error::RequestFailed { .. }) | Err(Error::ReqRespSizeBit { .. }) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Framebuffer of several pages flipped through the virtual offset
+///
+/// Draw into the back page and call `flip` to show it.
+#[derive(Debug)]
+pub struct PagedFramebuffer<'a> {
+    fb: Framebuffer<'a>,
+    pages: u32,
+    front: u32,
+    vsync: bool,
+    map: Option<MemoryMap>,
+    flips: u64,
+    vsyncs: u64,
+}
+
+impl<'a> PagedFramebuffer<'a> {
+    pub fn framebuffer(&self) -> &Framebuffer<'a> {
+        &self.fb
+    }
+
+    pub fn pages(&self) -> u32 {
+        self.pages
+    }
+
+    /// Index of the page currently shown
+    pub fn front_page(&self) -> u32 {
+        self.front
+    }
+
+    /// Index of the page shown by the next `flip`
+    pub fn back_page(&self) -> u32 {
+        next_page(self.front, self.pages)
+    }
+
+    /// Offset of `page` in bytes from the start of the buffer
+    ///
+    /// Returns `None` if `page` is not below `pages`.
+    pub fn page_offset(&self, page: u32) -> Option<usize> {
+        page_offset(page, self.pages, self.fb.physical.1, self.fb.pitch)
+    }
+
+    /// Size of a page in bytes
+    pub fn page_size(&self) -> usize {
+        self.fb.physical.1 as usize * self.fb.pitch as usize
+    }
+
+    /// Bus address of the first pixel of `page`, as seen by the VideoCore
+    ///
+    /// Returns `None` if `page` is not below `pages` or the address overflows.
+    pub fn page_bus_address(&self, page: u32) -> Option<u32> {
+        page_bus_address(self.fb.bus_address, self.page_offset(page)?)
+    }
+
+    /// Pixels of the back page
+    ///
+    /// The whole buffer is mapped on the first call.
+    pub fn back_page_mut(&mut self) -> Result<&mut [u8]> {
+        // The back page is always below `pages`
+        let offset = self.page_offset(self.back_page()).unwrap();
+        let size = self.page_size();
+        if self.map.is_none() {
+            self.map = Some(self.fb.map()?);
+        }
+        let map = self.map.as_mut().unwrap();
+        Ok(&mut map.as_mut_slice()[offset..offset + size])
+    }
+
+    /// Show the back page
+    ///
+    /// The virtual offset is moved to the back page and, if the firmware supports it,
+    /// the call waits for the vertical sync at which the page becomes visible.
+    /// The display of the framebuffer, if any, is selected and stays selected.
+    pub fn flip(&mut self) -> Result<()> {
+        let page = self.back_page();
+        let y = page * self.fb.physical.1;
+        self.fb.select_display()?;
+        let (_, applied) = set_virtual_offset(self.fb.mb, 0, y)?;
+        if applied != y {
+            return Err(Error::VirtualOffsetMismatch {
+                requested: y,
+                actual: applied,
+            });
+        }
+        self.fb.virtual_offset = (0, y);
+        self.front = page;
+        self.flips += 1;
+        if self.vsync {
+            wait_for_vsync(self.fb.mb)?;
+            self.vsyncs += 1;
+        }
+        Ok(())
+    }
+
+    /// Whether `flip` waits for vertical sync
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
+    /// Number of flips so far
+    pub fn flips(&self) -> u64 {
+        self.flips
+    }
+
+    /// Number of vertical syncs waited for so far
+    pub fn vsyncs(&self) -> u64 {
+        self.vsyncs
+    }
+}
+
+fn next_page(front: u32, pages: u32) -> u32 {
+    (front + 1) % pages
+}
+
+fn page_offset(page: u32, pages: u32, height: u32, pitch: u32) -> Option<usize> {
+    if page >= pages {
+        return None;
+    }
+    (page as usize)
+        .checked_mul(height as usize)?
+        .checked_mul(pitch as usize)
+}
+
+fn page_bus_address(base: u32, offset: usize) -> Option<u32> {
+    base.checked_add(u32::try_from(offset).ok()?)
+}
+
+/// A palette entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r, g, b }
+    }
+
+    /// From the firmware representation `0x00BBGGRR`
+    pub fn from_u32(value: u32) -> Self {
+        Rgb {
+            r: value as u8,
+            g: (value >> 8) as u8,
+            b: (value >> 16) as u8,
+        }
+    }
+
+    /// To the firmware representation `0x00BBGGRR`
+    pub fn to_u32(self) -> u32 {
+        self.r as u32 | (self.g as u32) << 8 | (self.b as u32) << 16
+    }
+}
+
+/// Get the 256 entries of the palette used in 8 bits depth
+pub fn get_palette(mb: &Mailbox) -> Result<Vec<Rgb>> {
+    use message::fb_get_palette::*;
+
+    let mut msg = Message { in_: In };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_GET_PALETTE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.palette.iter().map(|&v| Rgb::from_u32(v)).collect()) }
+}
+
+/// Set the palette entries from `offset` to `offset + colors.len() - 1`
+///
+/// Returns `Error::InvalidPalette` if the firmware rejects the entries.
+pub fn set_palette(mb: &Mailbox, offset: u32, colors: &[Rgb]) -> Result<()> {
+    palette_property(mb, RPI_FIRMWARE_FRAMEBUFFER_SET_PALETTE, offset, colors)
+}
+
+/// Check the palette entries as `set_palette` without applying them
+pub fn test_palette(mb: &Mailbox, offset: u32, colors: &[Rgb]) -> Result<()> {
+    palette_property(mb, RPI_FIRMWARE_FRAMEBUFFER_TEST_PALETTE, offset, colors)
+}
+
+fn palette_property(
+    mb: &Mailbox,
+    tag: rpi_firmware_property_tag,
+    offset: u32,
+    colors: &[Rgb],
+) -> Result<()> {
+    use message::fb_set_palette::*;
+
+    let length = check_palette(offset, colors.len())?;
+    let mut msg = Message {
+        in_: In {
+            offset,
+            length,
+            palette: pack_palette(colors),
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        tag,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    if unsafe { msg.out.status } != 0 {
+        return Err(Error::InvalidPalette { offset, length });
+    }
+    Ok(())
+}
+
+/// Check that `length` entries from `offset` are within the 256 entries of the palette
+///
+/// Returns the length as sent to the firmware.
+fn check_palette(offset: u32, length: usize) -> Result<u32> {
+    match u32::try_from(length) {
+        Ok(length) if length != 0 && offset <= 256 && length <= 256 - offset => Ok(length),
+        _ => Err(Error::InvalidPalette {
+            offset,
+            length: length.try_into().unwrap_or(u32::MAX),
+        }),
+    }
+}
+
+/// Firmware representation of `colors`, padded to 256 entries
+fn pack_palette(colors: &[Rgb]) -> [u32; 256] {
+    let mut palette = [0u32; 256];
+    for (v, c) in palette.iter_mut().zip(colors) {
+        *v = c.to_u32();
+    }
+    palette
+}
+
+/// Blank or unblank the screen
+///
+/// Returns whether the screen is blanked.
+pub fn blank_screen(mb: &Mailbox, blank: bool) -> Result<bool> {
+    use message::fb_blank::*;
+
+    let mut msg = Message {
+        in_: In {
+            state: blank as u32,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_BLANK,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.state & 1 != 0) }
+}
+
+/// Set the backlight level of the display, 0 to 255
+///
+/// Only displays with a backlight controlled by the firmware,
+/// such as the official touchscreen, support this.
+/// Returns the level set by the firmware.
+pub fn set_backlight(mb: &Mailbox, level: u32) -> Result<u32> {
+    use message::fb_set_backlight::*;
+
+    let mut msg = Message { in_: In { level } };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_FRAMEBUFFER_SET_BACKLIGHT,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.level) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn page_arithmetic() {
+        assert_eq!(next_page(0, 2), 1);
+        assert_eq!(next_page(1, 2), 0);
+        assert_eq!(next_page(0, 1), 0);
+        assert_eq!(next_page(2, 3), 0);
+
+        assert_eq!(page_offset(0, 3, 1080, 7680), Some(0));
+        assert_eq!(page_offset(2, 3, 1080, 7680), Some(2 * 1080 * 7680));
+    }
+
+    #[test]
+    fn page_out_of_range() {
+        assert_eq!(page_offset(3, 3, 1080, 7680), None);
+        assert_eq!(page_offset(u32::MAX, 3, 1080, 7680), None);
+        assert_eq!(page_offset(0, 0, 1080, 7680), None);
+
+        assert_eq!(page_bus_address(0xc000_0000, 0x1000), Some(0xc000_1000));
+        assert_eq!(page_bus_address(0xffff_f000, 0x1000), None);
+    }
+
+    #[test]
+    fn palette_range() {
+        assert_eq!(check_palette(0, 256).unwrap(), 256);
+        assert_eq!(check_palette(255, 1).unwrap(), 1);
+        assert!(check_palette(0, 0).is_err());
+        assert!(check_palette(256, 0).is_err());
+        assert!(check_palette(256, 1).is_err());
+        assert!(check_palette(200, 57).is_err());
+        assert!(check_palette(0, 257).is_err());
+        assert!(matches!(
+            check_palette(u32::MAX, 1),
+            Err(Error::InvalidPalette {
+                offset: u32::MAX,
+                length: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn palette_colors() {
+        let color = Rgb::new(0x12, 0x34, 0x56);
+        assert_eq!(color.to_u32(), 0x0056_3412);
+        assert_eq!(Rgb::from_u32(0xff56_3412), color);
+
+        let palette = pack_palette(&[color, Rgb::new(0xff, 0, 0)]);
+        assert_eq!(palette[..3], [0x0056_3412, 0x0000_00ff, 0]);
+    }
+}
@@ -0,0 +1,423 @@
+//! Thermal-aware clock governor
+//!
+//! `Governor` lowers clock rates step by step while the SoC is hot, throttled or
+//! short of voltage, and raises them again once it has cooled down.
+//! Temperatures are in thousandths of a degree Celsius, rates in Hz and voltages in micro-volts,
+//! as reported by the firmware.
+//!
+//! The firmware and the time source are abstracted by `Backend` and `Clock`,
+//! so the governor can be driven deterministically.
+//!
+
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::mailbox::Mailbox;
+use crate::raspberrypi_firmware::rpi_firmware_clk_id;
+use crate::throttled;
+
+/// Access to the firmware used by `Governor`
+pub trait Backend {
+    fn temperature(&self) -> Result<u32>;
+    fn throttled(&self) -> Result<throttled::Flags>;
+    fn voltage(&self, voltage_id: u32) -> Result<u32>;
+    fn clock_rate(&self, clock_id: rpi_firmware_clk_id) -> Result<u32>;
+    fn min_clock_rate(&self, clock_id: rpi_firmware_clk_id) -> Result<u32>;
+    fn max_clock_rate(&self, clock_id: rpi_firmware_clk_id) -> Result<u32>;
+    fn set_clock_rate(
+        &self,
+        clock_id: rpi_firmware_clk_id,
+        rate: u32,
+        skip_setting_turbo: bool,
+    ) -> Result<u32>;
+}
+
+impl Backend for Mailbox {
+    fn temperature(&self) -> Result<u32> {
+        crate::get_temperature(self)
+    }
+
+    fn throttled(&self) -> Result<throttled::Flags> {
+        crate::get_throttled(self).map(throttled::Flags::from_bits_retain)
+    }
+
+    fn voltage(&self, voltage_id: u32) -> Result<u32> {
+        crate::get_voltage(self, voltage_id)
+    }
+
+    fn clock_rate(&self, clock_id: rpi_firmware_clk_id) -> Result<u32> {
+        crate::get_clock_rate(self, clock_id as u32)
+    }
+
+    fn min_clock_rate(&self, clock_id: rpi_firmware_clk_id) -> Result<u32> {
+        crate::get_min_clock_rate(self, clock_id as u32)
+    }
+
+    fn max_clock_rate(&self, clock_id: rpi_firmware_clk_id) -> Result<u32> {
+        crate::get_max_clock_rate(self, clock_id as u32)
+    }
+
+    fn set_clock_rate(
+        &self,
+        clock_id: rpi_firmware_clk_id,
+        rate: u32,
+        skip_setting_turbo: bool,
+    ) -> Result<u32> {
+        crate::set_clock_rate(self, clock_id as u32, rate, skip_setting_turbo)
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for &B {
+    fn temperature(&self) -> Result<u32> {
+        (**self).temperature()
+    }
+
+    fn throttled(&self) -> Result<throttled::Flags> {
+        (**self).throttled()
+    }
+
+    fn voltage(&self, voltage_id: u32) -> Result<u32> {
+        (**self).voltage(voltage_id)
+    }
+
+    fn clock_rate(&self, clock_id: rpi_firmware_clk_id) -> Result<u32> {
+        (**self).clock_rate(clock_id)
+    }
+
+    fn min_clock_rate(&self, clock_id: rpi_firmware_clk_id) -> Result<u32> {
+        (**self).min_clock_rate(clock_id)
+    }
+
+    fn max_clock_rate(&self, clock_id: rpi_firmware_clk_id) -> Result<u32> {
+        (**self).max_clock_rate(clock_id)
+    }
+
+    fn set_clock_rate(
+        &self,
+        clock_id: rpi_firmware_clk_id,
+        rate: u32,
+        skip_setting_turbo: bool,
+    ) -> Result<u32> {
+        (**self).set_clock_rate(clock_id, rate, skip_setting_turbo)
+    }
+}
+
+/// Time source used by `Governor`
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// `Instant::now`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// How a clock is adjusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockPolicy {
+    pub clock_id: rpi_firmware_clk_id,
+    /// Amount lowered at a time
+    pub step_down: u32,
+    /// Amount raised at a time
+    pub step_up: u32,
+    /// Lowest rate set by the governor, in addition to the firmware minimum
+    pub floor: Option<u32>,
+    /// Highest rate set by the governor, in addition to the firmware maximum
+    pub ceiling: Option<u32>,
+}
+
+impl ClockPolicy {
+    /// Step by `step` in both directions between the firmware limits
+    pub fn new(clock_id: rpi_firmware_clk_id, step: u32) -> Self {
+        ClockPolicy {
+            clock_id,
+            step_down: step,
+            step_up: step,
+            floor: None,
+            ceiling: None,
+        }
+    }
+}
+
+/// Conditions under which the governor adjusts clocks
+///
+/// Clocks are lowered when any of the hot conditions holds and raised when
+/// the temperature is at or below `low_temperature` and no hot condition holds.
+/// Between the two temperatures the rates are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    /// Lower clocks at or above this temperature
+    pub high_temperature: u32,
+    /// Raise clocks at or below this temperature
+    pub low_temperature: u32,
+    /// Lower clocks while any of these throttled bits is set
+    pub throttled_mask: throttled::Flags,
+    /// Lower clocks while the voltage of `voltage_id` is below `min_voltage`
+    pub voltage_id: u32,
+    pub min_voltage: Option<u32>,
+    /// Minimum time between two adjustments
+    pub hold: Duration,
+    /// Passed to `set_clock_rate`
+    pub skip_setting_turbo: bool,
+    pub clocks: Vec<ClockPolicy>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            high_temperature: 80_000,
+            low_temperature: 70_000,
+            throttled_mask: throttled::Flags::THROTTLED | throttled::Flags::SOFT_TEMP_LIMIT,
+            voltage_id: 1,
+            min_voltage: None,
+            hold: Duration::from_secs(5),
+            skip_setting_turbo: true,
+            clocks: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Hold,
+    StepDown,
+    StepUp,
+}
+
+/// Result of `Governor::step`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub action: Action,
+    pub temperature: u32,
+    pub throttled: throttled::Flags,
+    pub voltage: Option<u32>,
+    /// `(clock_id, rate)` after the step, in the order of `Policy::clocks`
+    pub rates: Vec<(rpi_firmware_clk_id, u32)>,
+}
+
+/// Thermal-aware clock governor
+///
+/// Call `step` periodically.
+#[derive(Debug)]
+pub struct Governor<B, C = SystemClock> {
+    backend: B,
+    clock: C,
+    policy: Policy,
+    /// `(floor, ceiling)` for each of `policy.clocks`
+    limits: Vec<(u32, u32)>,
+    last_change: Option<Instant>,
+}
+
+impl<B: Backend> Governor<B> {
+    pub fn new(backend: B, policy: Policy) -> Result<Self> {
+        Self::with_clock(backend, SystemClock, policy)
+    }
+}
+
+impl<B: Backend, C: Clock> Governor<B, C> {
+    /// Create a governor reading the time from `clock`
+    ///
+    /// The firmware limits of the clocks are read here.
+    pub fn with_clock(backend: B, clock: C, policy: Policy) -> Result<Self> {
+        let limits = policy
+            .clocks
+            .iter()
+            .map(|c| {
+                let min = backend.min_clock_rate(c.clock_id)?;
+                let max = backend.max_clock_rate(c.clock_id)?;
+                let floor = c.floor.map_or(min, |f| f.max(min));
+                let ceiling = c.ceiling.map_or(max, |v| v.min(max)).max(floor);
+                Ok((floor, ceiling))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Governor {
+            backend,
+            clock,
+            policy,
+            limits,
+            last_change: None,
+        })
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Read the sensors and adjust the clocks once
+    pub fn step(&mut self) -> Result<Decision> {
+        let now = self.clock.now();
+        let temperature = self.backend.temperature()?;
+        let throttled = self.backend.throttled()?;
+        let voltage = match self.policy.min_voltage {
+            Some(_) => Some(self.backend.voltage(self.policy.voltage_id)?),
+            None => None,
+        };
+
+        let hot = self.policy.high_temperature <= temperature
+            || throttled.intersects(self.policy.throttled_mask)
+            || matches!((voltage, self.policy.min_voltage), (Some(v), Some(min)) if v < min);
+        let cool = !hot && temperature <= self.policy.low_temperature;
+        let held = self
+            .last_change
+            .is_some_and(|at| now.duration_since(at) < self.policy.hold);
+        let action = match (hot, cool) {
+            _ if held => Action::Hold,
+            (true, _) => Action::StepDown,
+            (_, true) => Action::StepUp,
+            _ => Action::Hold,
+        };
+
+        let mut changed = false;
+        let mut rates = Vec::with_capacity(self.policy.clocks.len());
+        for (c, &(floor, ceiling)) in self.policy.clocks.iter().zip(self.limits.iter()) {
+            let current = self.backend.clock_rate(c.clock_id)?;
+            let target = match action {
+                Action::Hold => current,
+                Action::StepDown => current.saturating_sub(c.step_down).max(floor),
+                Action::StepUp => current.saturating_add(c.step_up).min(ceiling),
+            };
+            let rate = if target != current {
+                changed = true;
+                self.backend
+                    .set_clock_rate(c.clock_id, target, self.policy.skip_setting_turbo)?
+            } else {
+                current
+            };
+            rates.push((c.clock_id, rate));
+        }
+        if changed {
+            self.last_change = Some(now);
+        }
+
+        Ok(Decision {
+            action,
+            temperature,
+            throttled,
+            voltage,
+            rates,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use crate::raspberrypi_firmware::rpi_firmware_clk_id::*;
+
+    struct FakeBackend {
+        temperature: Cell<u32>,
+        rates: RefCell<HashMap<rpi_firmware_clk_id, u32>>,
+    }
+
+    impl Backend for FakeBackend {
+        fn temperature(&self) -> Result<u32> {
+            Ok(self.temperature.get())
+        }
+        fn throttled(&self) -> Result<throttled::Flags> {
+            Ok(throttled::Flags::empty())
+        }
+        fn voltage(&self, _: u32) -> Result<u32> {
+            Ok(1_200_000)
+        }
+        fn clock_rate(&self, clock_id: rpi_firmware_clk_id) -> Result<u32> {
+            Ok(self.rates.borrow()[&clock_id])
+        }
+        fn min_clock_rate(&self, _: rpi_firmware_clk_id) -> Result<u32> {
+            Ok(600_000_000)
+        }
+        fn max_clock_rate(&self, _: rpi_firmware_clk_id) -> Result<u32> {
+            Ok(1_500_000_000)
+        }
+        fn set_clock_rate(&self, clock_id: rpi_firmware_clk_id, rate: u32, _: bool) -> Result<u32> {
+            self.rates.borrow_mut().insert(clock_id, rate);
+            Ok(rate)
+        }
+    }
+
+    struct FakeClock(Cell<Instant>);
+
+    impl Clock for &FakeClock {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+    }
+
+    impl FakeClock {
+        fn advance(&self, secs: u64) {
+            self.0.set(self.0.get() + Duration::from_secs(secs));
+        }
+    }
+
+    fn governor<'a>(
+        backend: &'a FakeBackend,
+        clock: &'a FakeClock,
+    ) -> Governor<&'a FakeBackend, &'a FakeClock> {
+        let policy = Policy {
+            hold: Duration::from_secs(2),
+            clocks: vec![ClockPolicy {
+                floor: Some(1_000_000_000),
+                ..ClockPolicy::new(RPI_FIRMWARE_ARM_CLK_ID, 300_000_000)
+            }],
+            ..Policy::default()
+        };
+        Governor::with_clock(backend, clock, policy).unwrap()
+    }
+
+    #[test]
+    fn steps_down_to_floor_and_holds() {
+        let backend = FakeBackend {
+            temperature: Cell::new(85_000),
+            rates: RefCell::new(HashMap::from([(RPI_FIRMWARE_ARM_CLK_ID, 1_500_000_000)])),
+        };
+        let clock = FakeClock(Cell::new(Instant::now()));
+        let mut gov = governor(&backend, &clock);
+
+        let d = gov.step().unwrap();
+        assert_eq!(d.action, Action::StepDown);
+        assert_eq!(d.rates, vec![(RPI_FIRMWARE_ARM_CLK_ID, 1_200_000_000)]);
+
+        clock.advance(1);
+        assert_eq!(gov.step().unwrap().action, Action::Hold);
+
+        clock.advance(2);
+        assert_eq!(
+            gov.step().unwrap().rates,
+            vec![(RPI_FIRMWARE_ARM_CLK_ID, 1_000_000_000)]
+        );
+        clock.advance(2);
+        assert_eq!(
+            gov.step().unwrap().rates,
+            vec![(RPI_FIRMWARE_ARM_CLK_ID, 1_000_000_000)]
+        );
+    }
+
+    #[test]
+    fn hysteresis_between_thresholds() {
+        let backend = FakeBackend {
+            temperature: Cell::new(75_000),
+            rates: RefCell::new(HashMap::from([(RPI_FIRMWARE_ARM_CLK_ID, 1_200_000_000)])),
+        };
+        let clock = FakeClock(Cell::new(Instant::now()));
+        let mut gov = governor(&backend, &clock);
+
+        assert_eq!(gov.step().unwrap().action, Action::Hold);
+
+        backend.temperature.set(65_000);
+        let d = gov.step().unwrap();
+        assert_eq!(d.action, Action::StepUp);
+        assert_eq!(d.rates, vec![(RPI_FIRMWARE_ARM_CLK_ID, 1_500_000_000)]);
+    }
+}
@@ -0,0 +1,309 @@
+//! GPIO expander driven by the firmware
+//!
+//! On Pi 3 and Pi 4 some lines, such as the activity LED and the camera power,
+//! are not wired to the SoC but to an expander the firmware controls.
+//! Its pins are addressed as `EXPANDER_BASE + pin` by the firmware; functions
+//! of this module take the expander relative `pin`, below `NUM_PINS`.
+//!
+//! With the `embedded-hal` feature, `Pin` implements the digital pin traits.
+//!
+
+use std::mem::size_of;
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Firmware GPIO number of the first expander pin
+pub const EXPANDER_BASE: u32 = 128;
+/// Number of pins of the expander
+pub const NUM_PINS: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Polarity {
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// Termination of the pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pull {
+    None,
+    Down,
+    Up,
+}
+
+/// Configuration of an expander pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Config {
+    pub direction: Direction,
+    pub polarity: Polarity,
+    pub pull: Pull,
+}
+
+impl Config {
+    pub fn input(pull: Pull) -> Self {
+        Config {
+            direction: Direction::Input,
+            polarity: Polarity::ActiveHigh,
+            pull,
+        }
+    }
+
+    pub fn output() -> Self {
+        Config {
+            direction: Direction::Output,
+            polarity: Polarity::ActiveHigh,
+            pull: Pull::None,
+        }
+    }
+}
+
+/// Firmware GPIO number of `pin`
+fn gpio_of(pin: u32) -> Result<u32> {
+    match pin {
+        pin if pin < NUM_PINS => Ok(EXPANDER_BASE + pin),
+        pin => Err(Error::InvalidGpioPin { pin }),
+    }
+}
+
+fn check(gpio: u32, status: u32) -> Result<()> {
+    match status {
+        0 => Ok(()),
+        status => Err(Error::GpioRejected { gpio, status }),
+    }
+}
+
+/// Get the logical state of `pin`
+pub fn get_gpio_state(mb: &Mailbox, pin: u32) -> Result<bool> {
+    use message::gpio_state::*;
+
+    let gpio = gpio_of(pin)?;
+    let mut msg = Message {
+        in_: In { gpio, state: 0 },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_GPIO_STATE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    let out = unsafe { msg.out };
+    check(gpio, out.status)?;
+    Ok(out.state != 0)
+}
+
+/// Drive `pin`, which must be configured as an output
+pub fn set_gpio_state(mb: &Mailbox, pin: u32, state: bool) -> Result<()> {
+    use message::gpio_state::*;
+
+    let gpio = gpio_of(pin)?;
+    let mut msg = Message {
+        in_: In {
+            gpio,
+            state: state as u32,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_SET_GPIO_STATE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    check(gpio, unsafe { msg.out.status })
+}
+
+pub fn get_gpio_config(mb: &Mailbox, pin: u32) -> Result<Config> {
+    use message::get_gpio_config::*;
+
+    let gpio = gpio_of(pin)?;
+    let mut msg = Message {
+        in_: In {
+            gpio,
+            direction: 0,
+            polarity: 0,
+            term_en: 0,
+            term_pull_up: 0,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_GPIO_CONFIG,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    let out = unsafe { msg.out };
+    check(gpio, out.status)?;
+    Ok(Config {
+        direction: match out.direction {
+            0 => Direction::Input,
+            _ => Direction::Output,
+        },
+        polarity: match out.polarity {
+            0 => Polarity::ActiveHigh,
+            _ => Polarity::ActiveLow,
+        },
+        pull: match (out.term_en, out.term_pull_up) {
+            (0, _) => Pull::None,
+            (_, 0) => Pull::Down,
+            _ => Pull::Up,
+        },
+    })
+}
+
+/// Configure `pin`
+///
+/// initial_state: driven at once if `pin` becomes an output
+pub fn set_gpio_config(mb: &Mailbox, pin: u32, config: &Config, initial_state: bool) -> Result<()> {
+    use message::set_gpio_config::*;
+
+    let gpio = gpio_of(pin)?;
+    let mut msg = Message {
+        in_: In {
+            gpio,
+            direction: (config.direction == Direction::Output) as u32,
+            polarity: (config.polarity == Polarity::ActiveLow) as u32,
+            term_en: (config.pull != Pull::None) as u32,
+            term_pull_up: (config.pull == Pull::Up) as u32,
+            state: initial_state as u32,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_SET_GPIO_CONFIG,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    check(gpio, unsafe { msg.out.status })
+}
+
+/// A pin of the expander
+#[derive(Debug, Clone, Copy)]
+pub struct Pin<'a> {
+    mb: &'a Mailbox,
+    pin: u32,
+}
+
+impl<'a> Pin<'a> {
+    /// Pin `pin` of the expander, left configured as it is
+    pub fn new(mb: &'a Mailbox, pin: u32) -> Result<Self> {
+        gpio_of(pin)?;
+        Ok(Pin { mb, pin })
+    }
+
+    /// Configure as an output driven to `initial_state`
+    pub fn output(mb: &'a Mailbox, pin: u32, initial_state: bool) -> Result<Self> {
+        set_gpio_config(mb, pin, &Config::output(), initial_state)?;
+        Ok(Pin { mb, pin })
+    }
+
+    /// Configure as an input with `pull`
+    pub fn input(mb: &'a Mailbox, pin: u32, pull: Pull) -> Result<Self> {
+        set_gpio_config(mb, pin, &Config::input(pull), false)?;
+        Ok(Pin { mb, pin })
+    }
+
+    /// Expander relative number
+    pub fn pin(&self) -> u32 {
+        self.pin
+    }
+
+    /// Number of the pin for the firmware
+    pub fn gpio(&self) -> u32 {
+        EXPANDER_BASE + self.pin
+    }
+
+    pub fn config(&self) -> Result<Config> {
+        get_gpio_config(self.mb, self.pin)
+    }
+
+    pub fn configure(&mut self, config: &Config, initial_state: bool) -> Result<()> {
+        set_gpio_config(self.mb, self.pin, config, initial_state)
+    }
+
+    pub fn state(&self) -> Result<bool> {
+        get_gpio_state(self.mb, self.pin)
+    }
+
+    pub fn set_state(&mut self, state: bool) -> Result<()> {
+        set_gpio_state(self.mb, self.pin, state)
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+mod hal {
+    use embedded_hal::digital::{
+        self, ErrorKind, ErrorType, InputPin, OutputPin, StatefulOutputPin,
+    };
+
+    use super::Pin;
+    use crate::error::Error;
+
+    impl digital::Error for Error {
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    impl<'a> ErrorType for Pin<'a> {
+        type Error = Error;
+    }
+
+    impl<'a> OutputPin for Pin<'a> {
+        fn set_low(&mut self) -> Result<(), Error> {
+            self.set_state(false)
+        }
+
+        fn set_high(&mut self) -> Result<(), Error> {
+            self.set_state(true)
+        }
+    }
+
+    impl<'a> StatefulOutputPin for Pin<'a> {
+        fn is_set_high(&mut self) -> Result<bool, Error> {
+            self.state()
+        }
+
+        fn is_set_low(&mut self) -> Result<bool, Error> {
+            self.state().map(|state| !state)
+        }
+    }
+
+    impl<'a> InputPin for Pin<'a> {
+        fn is_high(&mut self) -> Result<bool, Error> {
+            self.state()
+        }
+
+        fn is_low(&mut self) -> Result<bool, Error> {
+            self.state().map(|state| !state)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pin_range() {
+        assert_eq!(gpio_of(0).unwrap(), 128);
+        assert_eq!(gpio_of(NUM_PINS - 1).unwrap(), 135);
+        assert!(matches!(
+            gpio_of(NUM_PINS),
+            Err(Error::InvalidGpioPin { pin: 8 })
+        ));
+        assert!(gpio_of(u32::MAX).is_err());
+    }
+}
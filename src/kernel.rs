@@ -2,11 +2,13 @@
 //!
 
 use std::mem::size_of;
+use std::ops::{Deref, DerefMut};
 use std::os::unix::io::AsRawFd;
 use std::ptr::{self, NonNull};
 
 use log::*;
 use nix::libc::c_int;
+use zeroize::Zeroize;
 
 use crate::error::{Error, Result};
 use crate::mailbox::Mailbox;
@@ -34,11 +36,53 @@ mod ioctl {
     }
 }
 
-fn rpi_firmware_property_list(mb: &Mailbox, data: *mut u8, tag_size: usize) -> Result<c_int> {
+/// Buffer of a request, cleared on drop if it holds secret values
+struct Buffer<T: Copy + Default + Zeroize> {
+    buf: Vec<T>,
+    secret: bool,
+}
+
+impl<T: Copy + Default + Zeroize> Buffer<T> {
+    fn new(len: usize, secret: bool) -> Self {
+        Buffer {
+            buf: vec![T::default(); len],
+            secret,
+        }
+    }
+}
+
+impl<T: Copy + Default + Zeroize> Deref for Buffer<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.buf
+    }
+}
+
+impl<T: Copy + Default + Zeroize> DerefMut for Buffer<T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.buf
+    }
+}
+
+impl<T: Copy + Default + Zeroize> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        if self.secret {
+            self.buf.zeroize();
+        }
+    }
+}
+
+fn rpi_firmware_property_list(
+    mb: &Mailbox,
+    data: *mut u8,
+    tag_size: usize,
+    secret: bool,
+) -> Result<c_int> {
     let size: usize = size_of::<u32>() * 2 + tag_size + size_of::<u32>();
     debug!("{}:{}", size, tag_size);
 
-    let mut buf: Vec<u32> = vec![0u32; size / 4];
+    let mut buf = Buffer::<u32>::new(size / 4, secret);
     // make request
     buf[0] = size as u32;
     buf[1] = RPI_FIRMWARE_STATUS_REQUEST as u32;
@@ -48,13 +92,17 @@ fn rpi_firmware_property_list(mb: &Mailbox, data: *mut u8, tag_size: usize) -> R
     buf[size / 4 - 1] = RPI_FIRMWARE_PROPERTY_END as u32;
 
     // issue request to mailbox
-    debug!("buf: {:?}", buf);
+    if !secret {
+        debug!("buf: {:?}", *buf);
+    }
     #[cfg(not(target_pointer_width = "32"))]
     let ptr = buf.as_mut_ptr() as *mut *mut nix::libc::c_char;
     #[cfg(target_pointer_width = "32")]
     let ptr = buf.as_mut_ptr();
     let res = unsafe { ioctl::mailbox_property(mb.as_raw_fd(), ptr) }?;
-    debug!("buf: {:?}", buf);
+    if !secret {
+        debug!("buf: {:?}", *buf);
+    }
 
     if buf[1] != RPI_FIRMWARE_STATUS_SUCCESS as u32 {
         return Err(Error::RequestFailed { code: buf[1] });
@@ -73,6 +121,30 @@ pub fn rpi_firmware_property(
     tag_data: *mut u8,
     buf_size: usize,
     req_resp_size: usize,
+) -> Result<()> {
+    property(mb, tag, tag_data, buf_size, req_resp_size, false)
+}
+
+/// Same as `rpi_firmware_property` for secret values
+///
+/// The intermediate buffers are cleared before they are released and not logged.
+pub fn rpi_firmware_property_secret(
+    mb: &Mailbox,
+    tag: rpi_firmware_property_tag,
+    tag_data: *mut u8,
+    buf_size: usize,
+    req_resp_size: usize,
+) -> Result<()> {
+    property(mb, tag, tag_data, buf_size, req_resp_size, true)
+}
+
+fn property(
+    mb: &Mailbox,
+    tag: rpi_firmware_property_tag,
+    tag_data: *mut u8,
+    buf_size: usize,
+    req_resp_size: usize,
+    secret: bool,
 ) -> Result<()> {
     if buf_size < req_resp_size {
         return Err(Error::InvalidInput {
@@ -84,7 +156,7 @@ pub fn rpi_firmware_property(
     let data_size = size_of::<rpi_firmware_property_tag_header>() + buf_size;
     debug!("{},{},{}", buf_size, req_resp_size, data_size);
 
-    let mut data = vec![0u8; data_size];
+    let mut data = Buffer::<u8>::new(data_size, secret);
     union U {
         header: NonNull<rpi_firmware_property_tag_header>,
         data: NonNull<u8>,
@@ -111,7 +183,7 @@ pub fn rpi_firmware_property(
 
         // issue request
         debug!("data[..] {} {} {} {}", data[0], data[1], data[2], data[3]);
-        rpi_firmware_property_list(mb, u.data.as_ptr(), data_size)?;
+        rpi_firmware_property_list(mb, u.data.as_ptr(), data_size, secret)?;
         debug!("data[..] {} {} {} {}", data[0], data[1], data[2], data[3]);
 
         // check response header bit
@@ -165,3 +237,243 @@ pub fn rpi_firmware_property(
 
     Ok(())
 }
+
+/// A tag of a property list issued by `rpi_firmware_property_batch`
+pub struct PropertyTag<'a> {
+    pub tag: rpi_firmware_property_tag,
+    pub tag_data: &'a mut [u8],
+    pub req_resp_size: usize,
+}
+
+impl<'a> PropertyTag<'a> {
+    /// Use `msg` as the value buffer of `tag`
+    ///
+    /// # Safety
+    ///
+    /// Every byte of `msg` must be initialised, so a union whose `In` is smaller
+    /// than its `Out` must be created through `out`, and any bytes the firmware
+    /// writes must form a valid `T`. The `message::*::Message` unions, made of
+    /// `u32` fields only, meet the latter.
+    pub unsafe fn new<T: Copy>(
+        tag: rpi_firmware_property_tag,
+        msg: &'a mut T,
+        req_resp_size: usize,
+    ) -> Self {
+        let tag_data = std::slice::from_raw_parts_mut(msg as *mut T as *mut u8, size_of::<T>());
+        PropertyTag {
+            tag,
+            tag_data,
+            req_resp_size,
+        }
+    }
+}
+
+fn padded(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+/// Lay out `tags` as the tags of a property list
+fn pack_tags(tags: &[PropertyTag]) -> Result<Vec<u8>> {
+    let header_size = size_of::<rpi_firmware_property_tag_header>();
+
+    let mut data = Vec::new();
+    for t in tags.iter() {
+        let buf_size = t.tag_data.len();
+        if buf_size < t.req_resp_size {
+            return Err(Error::InvalidInput {
+                buf_size,
+                req_resp_size: t.req_resp_size,
+            });
+        }
+        let header = rpi_firmware_property_tag_header {
+            tag: t.tag,
+            buf_size: padded(buf_size) as u32,
+            req_resp_size: t.req_resp_size as u32,
+        };
+        let offset = data.len();
+        data.resize(offset + header_size + padded(buf_size), 0u8);
+        unsafe {
+            ptr::copy(
+                &header as *const rpi_firmware_property_tag_header as *const u8,
+                data.as_mut_ptr().add(offset),
+                header_size,
+            );
+        }
+        data[offset + header_size..offset + header_size + buf_size].copy_from_slice(t.tag_data);
+    }
+    Ok(data)
+}
+
+/// Check the responses in `data`, laid out by `pack_tags`, and copy them back to `tags`
+fn unpack_tags(data: &[u8], tags: &mut [PropertyTag]) -> Result<()> {
+    let header_size = size_of::<rpi_firmware_property_tag_header>();
+
+    let mut offset = 0;
+    for t in tags.iter_mut() {
+        let buf_size = t.tag_data.len();
+        let body = offset + header_size;
+        if data.len() < body + padded(buf_size) {
+            return Err(Error::BufferSizeMismatchSupplied {
+                req_resp_size: body + padded(buf_size),
+                supplied: data.len(),
+            });
+        }
+        let mut header = unsafe {
+            ptr::read_unaligned(data.as_ptr().add(offset) as *const rpi_firmware_property_tag_header)
+        };
+        if (header.req_resp_size & (1u32 << 31)) == 0 {
+            return Err(Error::ReqRespSizeBit {
+                req_resp_size: header.req_resp_size,
+            });
+        }
+        header.req_resp_size &= !(1u32 << 31); // clear flag
+
+        debug!(
+            "{:?} req_resp_size: {:x},{:x}",
+            t.tag, header.req_resp_size, t.req_resp_size
+        );
+        if header.req_resp_size != t.req_resp_size as u32 {
+            return Err(Error::BufferSizeMismatch {
+                req_resp_size: header.req_resp_size as usize,
+                think: t.req_resp_size,
+            });
+        }
+
+        t.tag_data[..t.req_resp_size].copy_from_slice(&data[body..body + t.req_resp_size]);
+        offset = body + padded(buf_size);
+    }
+    Ok(())
+}
+
+/// Issue several tags in one property list
+///
+/// Responses are checked in the same way as `rpi_firmware_property`.
+pub fn rpi_firmware_property_batch(mb: &Mailbox, tags: &mut [PropertyTag]) -> Result<()> {
+    let mut data = pack_tags(tags)?;
+    debug!("batch of {} tags, {} bytes", tags.len(), data.len());
+
+    rpi_firmware_property_list(mb, data.as_mut_ptr(), data.len(), false)?;
+
+    unpack_tags(&data, tags)
+}
+
+/// Issue a tag whose response length varies, such as a string
+///
+/// `tag_data` is the value buffer.
+/// Returns the length of the response, which exceeds `tag_data.len()`
+/// if the buffer was too small; in that case the response is truncated.
+pub fn rpi_firmware_property_varlen(
+    mb: &Mailbox,
+    tag: rpi_firmware_property_tag,
+    tag_data: &mut [u8],
+) -> Result<usize> {
+    let header_size = size_of::<rpi_firmware_property_tag_header>();
+    let buf_size = (tag_data.len() + 3) & !3;
+
+    let mut data = vec![0u8; header_size + buf_size];
+    let header = rpi_firmware_property_tag_header {
+        tag,
+        buf_size: buf_size as u32,
+        req_resp_size: 0,
+    };
+    unsafe {
+        ptr::copy(
+            &header as *const rpi_firmware_property_tag_header as *const u8,
+            data.as_mut_ptr(),
+            header_size,
+        );
+    }
+    data[header_size..header_size + tag_data.len()].copy_from_slice(tag_data);
+
+    rpi_firmware_property_list(mb, data.as_mut_ptr(), data.len(), false)?;
+
+    let header =
+        unsafe { ptr::read_unaligned(data.as_ptr() as *const rpi_firmware_property_tag_header) };
+    if (header.req_resp_size & (1u32 << 31)) == 0 {
+        return Err(Error::ReqRespSizeBit {
+            req_resp_size: header.req_resp_size,
+        });
+    }
+    let resp_size = (header.req_resp_size & !(1u32 << 31)) as usize;
+    debug!("{:?} resp_size: {}", tag, resp_size);
+
+    let n = resp_size.min(tag_data.len());
+    tag_data[..n].copy_from_slice(&data[header_size..header_size + n]);
+    Ok(resp_size)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn words(data: &[u8]) -> Vec<u32> {
+        data.chunks_exact(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn pack_and_unpack() {
+        let mut a = [1u8, 2, 3, 4, 5, 6];
+        let mut b = [7u32];
+        let mut tags = unsafe {
+            [
+                PropertyTag::new(RPI_FIRMWARE_GET_BOARD_SERIAL, &mut a, 4),
+                PropertyTag::new(RPI_FIRMWARE_GET_BOARD_MODEL, &mut b, 4),
+            ]
+        };
+        let mut data = pack_tags(&tags).unwrap();
+        assert_eq!(
+            words(&data),
+            vec![0x10004, 8, 4, 0x0403_0201, 0x0605, 0x10001, 4, 4, 7]
+        );
+
+        // responses as written by the firmware
+        data[8..12].copy_from_slice(&(4u32 | 1 << 31).to_le_bytes());
+        data[12..16].copy_from_slice(&0xaabb_ccddu32.to_le_bytes());
+        data[28..32].copy_from_slice(&(4u32 | 1 << 31).to_le_bytes());
+        data[32..36].copy_from_slice(&42u32.to_le_bytes());
+        unpack_tags(&data, &mut tags).unwrap();
+        assert_eq!(a, [0xdd, 0xcc, 0xbb, 0xaa, 5, 6]);
+        assert_eq!(b, [42]);
+    }
+
+    #[test]
+    fn unpack_errors() {
+        let mut a = [0u32];
+        let mut tags = unsafe { [PropertyTag::new(RPI_FIRMWARE_GET_BOARD_MODEL, &mut a, 4)] };
+        let mut data = pack_tags(&tags).unwrap();
+        assert!(matches!(
+            unpack_tags(&data, &mut tags),
+            Err(Error::ReqRespSizeBit { req_resp_size: 4 })
+        ));
+        data[8..12].copy_from_slice(&(8u32 | 1 << 31).to_le_bytes());
+        assert!(matches!(
+            unpack_tags(&data, &mut tags),
+            Err(Error::BufferSizeMismatch {
+                req_resp_size: 8,
+                think: 4
+            })
+        ));
+        assert!(matches!(
+            unpack_tags(&data[..8], &mut tags),
+            Err(Error::BufferSizeMismatchSupplied { .. })
+        ));
+
+        let mut short = [0u8; 2];
+        let tags = unsafe {
+            [PropertyTag::new(
+                RPI_FIRMWARE_GET_BOARD_MODEL,
+                &mut short,
+                4,
+            )]
+        };
+        assert!(matches!(
+            pack_tags(&tags),
+            Err(Error::InvalidInput {
+                buf_size: 2,
+                req_resp_size: 4
+            })
+        ));
+    }
+}
@@ -1,12 +1,35 @@
 //! A RaspberryPi mailbox interface
 //!
 
+pub mod capture;
+pub mod clock;
+pub mod cmdline;
+pub mod cursor;
+pub mod display;
+pub mod dispmanx;
+pub mod dma;
+pub mod domain;
+pub mod edid;
 pub mod error;
+pub mod framebuffer;
+pub mod governor;
+pub mod gpio;
 mod kernel;
 mod mailbox;
 pub mod memflag;
+pub mod memory;
 mod message;
+pub mod mmap;
+pub mod otp;
+pub mod periph;
+pub mod qpu;
 pub mod raspberrypi_firmware;
+pub mod screensaver;
+pub mod stc;
+pub mod telemetry;
+pub mod throttled;
+pub mod vchiq;
+pub mod vpu;
 
 use std::mem::size_of;
 
@@ -254,11 +277,49 @@ pub fn get_clock_rate(mb: &Mailbox, clock_id: u32) -> Result<u32> {
     unsafe { Ok(msg.out.rate) }
 }
 
+pub fn get_max_clock_rate(mb: &Mailbox, clock_id: u32) -> Result<u32> {
+    use message::max_clock_rate::*;
+
+    let mut msg = Message {
+        in_: In { clock_id },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_MAX_CLOCK_RATE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.rate) }
+}
+
+pub fn get_min_clock_rate(mb: &Mailbox, clock_id: u32) -> Result<u32> {
+    use message::min_clock_rate::*;
+
+    let mut msg = Message {
+        in_: In { clock_id },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_MIN_CLOCK_RATE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.rate) }
+}
+
+/// Set the rate of `clock_id` and return the rate actually set by the firmware
+///
+/// Unless `skip_setting_turbo` is true, setting the ARM clock above its default
+/// rate enables turbo mode (see `set_turbo`), which also raises the core clock and voltage.
+/// With `skip_setting_turbo`, the firmware leaves turbo mode alone and may clamp
+/// the rate to the non-turbo maximum, so compare the returned rate with `rate`.
 pub fn set_clock_rate(
     mb: &Mailbox,
     clock_id: u32,
     rate: u32,
-    skip_setting_turbo: u32,
+    skip_setting_turbo: bool,
 ) -> Result<u32> {
     use message::set_clock_rate::*;
 
@@ -266,7 +327,7 @@ pub fn set_clock_rate(
         in_: In {
             clock_id,
             rate,
-            skip_setting_turbo,
+            skip_setting_turbo: skip_setting_turbo as u32,
         },
     };
     rpi_firmware_property(
@@ -278,3 +339,86 @@ pub fn set_clock_rate(
     )?;
     unsafe { Ok(msg.out.rate) }
 }
+
+/// Get the voltage of `voltage_id` in micro-volts
+///
+/// voltage_id: 1 (core), 2 (sdram_c), 3 (sdram_p) or 4 (sdram_i)
+pub fn get_voltage(mb: &Mailbox, voltage_id: u32) -> Result<u32> {
+    use message::voltage::*;
+
+    let mut msg = Message {
+        in_: In { voltage_id },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_VOLTAGE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.value) }
+}
+
+/// Get the SoC temperature in thousandths of a degree Celsius
+pub fn get_temperature(mb: &Mailbox) -> Result<u32> {
+    use message::temperature::*;
+
+    let mut msg = Message {
+        in_: In { temperature_id: 0 },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_TEMPERATURE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.value) }
+}
+
+/// The only turbo id known to the firmware
+pub const TURBO_ID: u32 = 0;
+
+/// Get whether turbo mode is enabled
+pub fn get_turbo(mb: &Mailbox) -> Result<bool> {
+    use message::turbo::*;
+
+    let mut msg = Message {
+        in_: In { turbo_id: TURBO_ID },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_TURBO,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.level != 0) }
+}
+
+/// Enable or disable turbo mode
+///
+/// Turbo mode runs the ARM, core and V3D clocks at their maximum rates,
+/// so `get_clock_rate` reports different rates afterwards.
+/// Returns `Error::TurboMismatch` if the firmware did not apply the requested level.
+pub fn set_turbo(mb: &Mailbox, enable: bool) -> Result<()> {
+    use message::set_turbo::*;
+
+    let mut msg = Message {
+        in_: In {
+            turbo_id: TURBO_ID,
+            level: enable as u32,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_SET_TURBO,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    if unsafe { msg.out.level != 0 } != enable {
+        return Err(error::Error::TurboMismatch { requested: enable });
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! VideoCore memory owned by the process
+//!
+
+use log::*;
+
+use crate::error::Result;
+use crate::mailbox::Mailbox;
+use crate::memflag;
+use crate::mmap::MemoryMap;
+use crate::{mailbox_mem_alloc, mailbox_mem_free, mailbox_mem_lock, mailbox_mem_unlock};
+
+/// Memory allocated by `mailbox_mem_alloc` and locked by `mailbox_mem_lock`
+///
+/// The memory is unlocked and released on drop.
+#[derive(Debug)]
+pub struct LockedMemory<'a> {
+    mb: &'a Mailbox,
+    handle: u32,
+    busaddr: u32,
+    size: u32,
+}
+
+impl<'a> LockedMemory<'a> {
+    pub fn new(mb: &'a Mailbox, size: u32, align: u32, flags: memflag::Flags) -> Result<Self> {
+        let handle = mailbox_mem_alloc(mb, size, align, flags)?;
+        let busaddr = match mailbox_mem_lock(mb, handle) {
+            Ok(busaddr) => busaddr,
+            Err(err) => {
+                if let Err(err) = mailbox_mem_free(mb, handle) {
+                    error!("failed to release handle {}: {}", handle, err);
+                }
+                return Err(err);
+            }
+        };
+        Ok(LockedMemory {
+            mb,
+            handle,
+            busaddr,
+            size,
+        })
+    }
+
+    pub fn mailbox(&self) -> &'a Mailbox {
+        self.mb
+    }
+
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+
+    pub fn bus_address(&self) -> u32 {
+        self.busaddr
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Map the memory into the process
+    pub fn map(&self) -> Result<MemoryMap> {
+        MemoryMap::new(self.busaddr, self.size as usize)
+    }
+}
+
+impl<'a> Drop for LockedMemory<'a> {
+    fn drop(&mut self) {
+        if let Err(err) = mailbox_mem_unlock(self.mb, self.busaddr) {
+            error!("failed to unlock 0x{:08x}: {}", self.busaddr, err);
+        }
+        if let Err(err) = mailbox_mem_free(self.mb, self.handle) {
+            error!("failed to release handle {}: {}", self.handle, err);
+        }
+    }
+}
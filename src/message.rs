@@ -358,3 +358,974 @@ pub mod set_clock_rate {
         pub out: Out,
     }
 }
+
+/// RPI_FIRMWARE_GET_VOLTAGE
+pub mod voltage {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub voltage_id: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub voltage_id: u32,
+        pub value: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_TEMPERATURE
+pub mod temperature {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub temperature_id: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub temperature_id: u32,
+        pub value: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_TURBO
+pub mod turbo {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub turbo_id: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub turbo_id: u32,
+        pub level: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_SET_TURBO
+pub mod set_turbo {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub turbo_id: u32,
+        pub level: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub turbo_id: u32,
+        pub level: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_MAX_CLOCK_RATE
+pub mod max_clock_rate {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub clock_id: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub clock_id: u32,
+        pub rate: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_MIN_CLOCK_RATE
+pub mod min_clock_rate {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub clock_id: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub clock_id: u32,
+        pub rate: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_ALLOCATE
+pub mod fb_allocate {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub alignment: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub base: u32,
+        pub size: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_RELEASE
+pub mod fb_release {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_{GET,TEST,SET}_PHYSICAL_WIDTH_HEIGHT
+pub mod fb_physical_width_height {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub width: u32,
+        pub height: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub width: u32,
+        pub height: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_{GET,TEST,SET}_VIRTUAL_WIDTH_HEIGHT
+pub mod fb_virtual_width_height {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub width: u32,
+        pub height: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub width: u32,
+        pub height: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_{GET,TEST,SET}_DEPTH
+pub mod fb_depth {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub depth: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub depth: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_{GET,TEST,SET}_PIXEL_ORDER
+pub mod fb_pixel_order {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_{GET,TEST,SET}_ALPHA_MODE
+pub mod fb_alpha_mode {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_GET_PITCH
+pub mod fb_pitch {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub pitch: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_{GET,TEST,SET}_VIRTUAL_OFFSET
+pub mod fb_virtual_offset {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub x: u32,
+        pub y: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub x: u32,
+        pub y: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_{GET,TEST,SET}_OVERSCAN
+pub mod fb_overscan {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub top: u32,
+        pub bottom: u32,
+        pub left: u32,
+        pub right: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub top: u32,
+        pub bottom: u32,
+        pub left: u32,
+        pub right: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_{TEST,SET}_VSYNC
+pub mod fb_vsync {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub dummy: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub dummy: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_GET_PALETTE
+pub mod fb_get_palette {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub palette: [u32; 256],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_{TEST,SET}_PALETTE
+pub mod fb_set_palette {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub offset: u32,
+        pub length: u32,
+        pub palette: [u32; 256],
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub status: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_SET_CURSOR_INFO
+pub mod set_cursor_info {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub width: u32,
+        pub height: u32,
+        pub unused: u32,
+        pub pixels: u32,
+        pub hotspot_x: u32,
+        pub hotspot_y: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub status: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_SET_CURSOR_STATE
+pub mod set_cursor_state {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub enable: u32,
+        pub x: u32,
+        pub y: u32,
+        pub flags: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub status: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_EDID_BLOCK
+pub mod edid_block {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub block_num: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub block_num: u32,
+        pub status: u32,
+        pub edid: [u8; 128],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_EDID_BLOCK_DISPLAY
+pub mod edid_block_display {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub block_num: u32,
+        pub display_num: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub block_num: u32,
+        pub status: u32,
+        pub edid: [u8; 128],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_GET_NUM_DISPLAYS
+pub mod fb_num_displays {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub num_displays: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_GET_DISPLAY_SETTINGS
+pub mod fb_display_settings {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub display_num: u32,
+    }
+
+    /// Layout of `vc4_display_settings_t` in the bcm2708_fb driver
+    #[repr(C, packed)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub display_num: u32,
+        pub width: u32,
+        pub height: u32,
+        pub depth: u32,
+        pub pitch: u16,
+        pub virtual_width: u32,
+        pub virtual_height: u32,
+        pub virtual_width_offset: u16,
+        pub virtual_height_offset: u32,
+        pub fb_bus_address: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_GET_DISPLAY_ID
+pub mod fb_display_id {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub display_num: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub display_id: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_SET_DISPLAY_NUM
+pub mod fb_set_display_num {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub display_num: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub display_num: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_BLANK
+pub mod fb_blank {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_FRAMEBUFFER_SET_BACKLIGHT
+pub mod fb_set_backlight {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub level: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub level: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_DMA_CHANNELS
+pub mod dma_channels {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub mask: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_GPIO_STATE
+/// RPI_FIRMWARE_SET_GPIO_STATE
+pub mod gpio_state {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub gpio: u32,
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub status: u32,
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_GPIO_CONFIG
+pub mod get_gpio_config {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub gpio: u32,
+        pub direction: u32,
+        pub polarity: u32,
+        pub term_en: u32,
+        pub term_pull_up: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub status: u32,
+        pub direction: u32,
+        pub polarity: u32,
+        pub term_en: u32,
+        pub term_pull_up: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_SET_GPIO_CONFIG
+pub mod set_gpio_config {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub gpio: u32,
+        pub direction: u32,
+        pub polarity: u32,
+        pub term_en: u32,
+        pub term_pull_up: u32,
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub status: u32,
+        pub direction: u32,
+        pub polarity: u32,
+        pub term_en: u32,
+        pub term_pull_up: u32,
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_PERIPH_REG
+/// RPI_FIRMWARE_SET_PERIPH_REG
+pub mod periph_reg {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub reg: u32,
+        pub value: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub reg: u32,
+        pub value: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_DOMAIN_STATE
+/// RPI_FIRMWARE_SET_DOMAIN_STATE
+pub mod domain_state {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub domain: u32,
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub domain: u32,
+        pub state: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_STC
+pub mod stc {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub stc_lo: u32,
+        pub stc_hi: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_EXECUTE_CODE
+pub mod execute_code {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub code: u32,
+        pub r0: u32,
+        pub r1: u32,
+        pub r2: u32,
+        pub r3: u32,
+        pub r4: u32,
+        pub r5: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub r0: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_SET_ENABLE_QPU
+pub mod enable_qpu {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub enable: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub status: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_EXECUTE_QPU
+pub mod execute_qpu {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub num_qpus: u32,
+        pub control: u32,
+        pub noflush: u32,
+        pub timeout: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub status: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_GET_DISPMANX_RESOURCE_MEM_HANDLE
+pub mod dispmanx_resource_mem_handle {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub resource: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub status: u32,
+        pub mem_handle: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
+
+/// RPI_FIRMWARE_VCHIQ_INIT
+pub mod vchiq_init {
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct In {
+        pub slot_busaddr: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Out {
+        pub status: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Message {
+        pub in_: In,
+        pub out: Out,
+    }
+}
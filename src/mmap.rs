@@ -0,0 +1,122 @@
+//! Mapping of memory shared with the VideoCore
+//!
+//! Memory is mapped through `/dev/mem`, which usually requires root.
+//!
+
+use std::num::NonZeroUsize;
+use std::os::unix::io::AsRawFd;
+use std::ptr::NonNull;
+use std::slice;
+
+use log::*;
+use nix::fcntl;
+use nix::sys::mman::{self, MapFlags, ProtFlags};
+use nix::sys::stat;
+use nix::unistd::{self, SysconfVar};
+use nix::NixPath;
+
+use crate::error::Result;
+
+/// Convert a bus address returned by the firmware to an ARM physical address
+pub fn bus_to_phys(busaddr: u32) -> u32 {
+    busaddr & !0xC000_0000
+}
+
+/// Memory mapped from a bus address
+#[derive(Debug)]
+pub struct MemoryMap {
+    base: NonNull<u8>,
+    map_len: usize,
+    offset: usize,
+    len: usize,
+}
+
+unsafe impl Send for MemoryMap {}
+
+impl MemoryMap {
+    /// Map `len` bytes at `busaddr` through `/dev/mem`
+    pub fn new(busaddr: u32, len: usize) -> Result<Self> {
+        Self::with_device("/dev/mem", busaddr, len)
+    }
+
+    /// Map `len` bytes at `busaddr` through `device`
+    pub fn with_device<P>(device: &P, busaddr: u32, len: usize) -> Result<Self>
+    where
+        P: ?Sized + NixPath,
+    {
+        let page_size = unistd::sysconf(SysconfVar::PAGE_SIZE)?.unwrap_or(4096) as usize;
+        let phys = bus_to_phys(busaddr) as usize;
+        let offset = phys % page_size;
+        let map_len = offset + len.max(1);
+
+        let fd = fcntl::open(
+            device,
+            fcntl::OFlag::O_RDWR | fcntl::OFlag::O_SYNC,
+            stat::Mode::empty(),
+        )?;
+        let file = Fd(fd);
+        let ptr = unsafe {
+            mman::mmap(
+                None,
+                NonZeroUsize::new(map_len).unwrap(),
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+                file.as_raw_fd(),
+                (phys - offset) as nix::libc::off_t,
+            )?
+        };
+        Ok(MemoryMap {
+            base: NonNull::new(ptr as *mut u8).unwrap(),
+            map_len,
+            offset,
+            len,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_ptr(&self) -> *const u8 {
+        unsafe { self.base.as_ptr().add(self.offset) }
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        unsafe { self.base.as_ptr().add(self.offset) }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
+    }
+}
+
+impl Drop for MemoryMap {
+    fn drop(&mut self) {
+        if let Err(err) = unsafe { mman::munmap(self.base.as_ptr() as *mut _, self.map_len) } {
+            error!("munmap: {}", err);
+        }
+    }
+}
+
+/// Closes the descriptor of the device once mapped
+struct Fd(i32);
+
+impl AsRawFd for Fd {
+    fn as_raw_fd(&self) -> i32 {
+        self.0
+    }
+}
+
+impl Drop for Fd {
+    fn drop(&mut self) {
+        unistd::close(self.0).ok();
+    }
+}
@@ -0,0 +1,285 @@
+//! One time programmable memory
+//!
+//! Programmed bits can never be cleared again, so every write requires a
+//! `ConfirmIrreversible` token and is verified by reading back.
+//! This covers the customer rows and the device private key of Pi 4 and CM4.
+//!
+
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property_secret;
+use crate::mailbox::Mailbox;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::{self, *};
+
+/// Number of customer OTP rows
+pub const CUSTOMER_OTP_ROWS: u32 = 8;
+
+/// `start` and `count` of the request locking the customer OTP
+const LOCK_START: u32 = 0xffff_ffff;
+const LOCK_COUNT: u32 = 0xaffe_0000;
+
+/// Token required by the functions that permanently program OTP
+#[derive(Debug)]
+pub struct ConfirmIrreversible(());
+
+impl ConfirmIrreversible {
+    /// Confirm that the caller knows OTP writes can never be undone
+    pub fn i_understand_otp_writes_are_permanent() -> Self {
+        ConfirmIrreversible(())
+    }
+}
+
+/// Issue an OTP tag laid out as `start`, `count` and `count` rows
+///
+/// The message buffers are cleared before they are released as rows may be secret.
+fn otp_property(
+    mb: &Mailbox,
+    tag: rpi_firmware_property_tag,
+    start: u32,
+    count: u32,
+    rows: &mut [u32],
+) -> Result<()> {
+    let mut buf = Zeroizing::new(vec![0u32; 2 + rows.len()]);
+    buf[0] = start;
+    buf[1] = count;
+    buf[2..].copy_from_slice(rows);
+    let size = buf.len() * 4;
+    let ret = rpi_firmware_property_secret(mb, tag, buf.as_mut_ptr() as *mut u8, size, size);
+    if ret.is_ok() {
+        rows.copy_from_slice(&buf[2..]);
+    }
+    ret
+}
+
+pub(crate) fn check_range(start: u32, count: usize, rows: u32) -> Result<()> {
+    if rows < start || ((rows - start) as usize) < count {
+        return Err(Error::InvalidOtpRange {
+            start,
+            count: count as u32,
+        });
+    }
+    Ok(())
+}
+
+/// Check that programming `requested` over `current` only sets bits
+pub(crate) fn check_only_sets(start: u32, current: &[u32], requested: &[u32]) -> Result<()> {
+    for (i, (&current, &requested)) in current.iter().zip(requested).enumerate() {
+        if current & !requested != 0 {
+            return Err(Error::OtpBitsCleared {
+                row: start + i as u32,
+                current,
+                requested,
+            });
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn verify(start: u32, expected: &[u32], actual: &[u32]) -> Result<()> {
+    for (i, (&expected, &actual)) in expected.iter().zip(actual).enumerate() {
+        if expected != actual {
+            return Err(Error::OtpVerifyFailed {
+                row: start + i as u32,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Read `count` customer OTP rows from `start`
+pub fn get_customer_otp(mb: &Mailbox, start: u32, count: u32) -> Result<Vec<u32>> {
+    check_range(start, count as usize, CUSTOMER_OTP_ROWS)?;
+    let mut rows = vec![0u32; count as usize];
+    otp_property(mb, RPI_FIRMWARE_GET_CUSTOMER_OTP, start, count, &mut rows)?;
+    Ok(rows)
+}
+
+/// Program `values` into the customer OTP rows from `start`
+///
+/// Fails without writing if a bit already programmed would have to be cleared.
+/// Rows are read back after the write and compared with `values`.
+pub fn set_customer_otp(
+    mb: &Mailbox,
+    start: u32,
+    values: &[u32],
+    _confirm: &ConfirmIrreversible,
+) -> Result<()> {
+    check_range(start, values.len(), CUSTOMER_OTP_ROWS)?;
+    let count = values.len() as u32;
+    let current = get_customer_otp(mb, start, count)?;
+    check_only_sets(start, &current, values)?;
+    if current == values {
+        return Ok(());
+    }
+    let mut rows = values.to_vec();
+    otp_property(mb, RPI_FIRMWARE_SET_CUSTOMER_OTP, start, count, &mut rows)?;
+    verify(start, values, &get_customer_otp(mb, start, count)?)
+}
+
+/// Lock the customer OTP against any further programming
+pub fn lock_customer_otp(mb: &Mailbox, _confirm: &ConfirmIrreversible) -> Result<()> {
+    otp_property(
+        mb,
+        RPI_FIRMWARE_SET_CUSTOMER_OTP,
+        LOCK_START,
+        LOCK_COUNT,
+        &mut [],
+    )
+}
+
+/// Number of OTP rows of the device private key
+pub const PRIVATE_KEY_ROWS: u32 = 8;
+
+/// 256 bits device private key, cleared from memory on drop
+///
+/// Bytes are in the order of the rows, each row big endian.
+/// Keys compare in constant time.
+pub struct PrivateKey([u8; 32]);
+
+impl PrivateKey {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        PrivateKey(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Whether no bit is set, as read from an unprogrammed key
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|&b| b == 0)
+    }
+
+    fn from_rows(rows: &[u32]) -> Self {
+        let mut key = PrivateKey([0; 32]);
+        for (dst, row) in key.0.chunks_exact_mut(4).zip(rows) {
+            dst.copy_from_slice(&row.to_be_bytes());
+        }
+        key
+    }
+
+    fn to_rows(&self) -> Zeroizing<[u32; PRIVATE_KEY_ROWS as usize]> {
+        let mut rows = Zeroizing::new([0u32; PRIVATE_KEY_ROWS as usize]);
+        for (row, src) in rows.iter_mut().zip(self.0.chunks_exact(4)) {
+            *row = u32::from_be_bytes([src[0], src[1], src[2], src[3]]);
+        }
+        rows
+    }
+}
+
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "PrivateKey(..)")
+    }
+}
+
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ct_eq(&other.0).into()
+    }
+}
+
+impl Eq for PrivateKey {}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for PrivateKey {}
+
+/// Read the device private key (Pi 4 and CM4)
+pub fn get_private_key(mb: &Mailbox) -> Result<PrivateKey> {
+    let mut rows = Zeroizing::new([0u32; PRIVATE_KEY_ROWS as usize]);
+    otp_property(
+        mb,
+        RPI_FIRMWARE_GET_PRIVATE_KEY,
+        0,
+        PRIVATE_KEY_ROWS,
+        &mut *rows,
+    )?;
+    Ok(PrivateKey::from_rows(&*rows))
+}
+
+/// Whether the device private key has been programmed
+pub fn is_private_key_programmed(mb: &Mailbox) -> Result<bool> {
+    get_private_key(mb).map(|key| !key.is_zero())
+}
+
+/// Program the device private key
+///
+/// The key can be programmed only once; fails with `Error::PrivateKeyProgrammed`
+/// if any bit is already set. The key is read back after the write.
+pub fn set_private_key(
+    mb: &Mailbox,
+    key: &PrivateKey,
+    _confirm: &ConfirmIrreversible,
+) -> Result<()> {
+    if is_private_key_programmed(mb)? {
+        return Err(Error::PrivateKeyProgrammed);
+    }
+    let mut rows = key.to_rows();
+    otp_property(
+        mb,
+        RPI_FIRMWARE_SET_PRIVATE_KEY,
+        0,
+        PRIVATE_KEY_ROWS,
+        &mut *rows,
+    )?;
+    if get_private_key(mb)? != *key {
+        return Err(Error::PrivateKeyVerifyFailed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn program_checks() {
+        assert!(check_range(0, 8, CUSTOMER_OTP_ROWS).is_ok());
+        assert!(check_range(7, 1, CUSTOMER_OTP_ROWS).is_ok());
+        assert!(matches!(
+            check_range(6, 3, CUSTOMER_OTP_ROWS),
+            Err(Error::InvalidOtpRange { start: 6, count: 3 })
+        ));
+        assert!(check_range(9, 0, CUSTOMER_OTP_ROWS).is_err());
+
+        assert!(check_only_sets(2, &[0x0000_00f0, 0], &[0x0000_00ff, 1]).is_ok());
+        assert!(matches!(
+            check_only_sets(2, &[0, 0x0000_00f0], &[0, 0x0000_000f]),
+            Err(Error::OtpBitsCleared { row: 3, .. })
+        ));
+
+        assert!(matches!(
+            verify(4, &[1, 2], &[1, 3]),
+            Err(Error::OtpVerifyFailed {
+                row: 5,
+                expected: 2,
+                actual: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn private_key_rows() {
+        let mut bytes = [0u8; 32];
+        bytes[0] = 0x12;
+        bytes[3] = 0x34;
+        bytes[31] = 0x56;
+        let key = PrivateKey::from_bytes(bytes);
+        let rows = key.to_rows();
+        assert_eq!(rows[0], 0x1200_0034);
+        assert_eq!(rows[7], 0x0000_0056);
+        assert_eq!(PrivateKey::from_rows(&*rows), key);
+        assert_ne!(PrivateKey::from_bytes([0; 32]), key);
+        assert!(!key.is_zero());
+        assert_eq!(format!("{:?}", key), "PrivateKey(..)");
+    }
+}
@@ -0,0 +1,153 @@
+//! Peripheral registers accessed through the firmware
+//!
+//! The firmware reads and writes the registers it exposes on behalf of the
+//! ARM, so neither root nor a `/dev/mem` mapping is needed.
+//!
+
+use std::fmt;
+use std::mem::size_of;
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Identifier of a register known to the firmware
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RegisterId(pub u32);
+
+impl fmt::Display for RegisterId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+/// Read the register `reg`
+pub fn get_periph_reg(mb: &Mailbox, reg: RegisterId) -> Result<u32> {
+    use message::periph_reg::*;
+
+    let mut msg = Message {
+        in_: In {
+            reg: reg.0,
+            value: 0,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_PERIPH_REG,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.value) }
+}
+
+fn set_periph_reg(mb: &Mailbox, reg: RegisterId, value: u32) -> Result<u32> {
+    use message::periph_reg::*;
+
+    let mut msg = Message {
+        in_: In { reg: reg.0, value },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_SET_PERIPH_REG,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.value) }
+}
+
+/// Access to the peripheral registers
+///
+/// Writes fail with `Error::PeriphWriteNotAllowed` unless the access was
+/// created with `PeriphRegs::writable`.
+#[derive(Debug, Clone, Copy)]
+pub struct PeriphRegs<'a> {
+    mb: &'a Mailbox,
+    writable: bool,
+}
+
+impl<'a> PeriphRegs<'a> {
+    pub fn read_only(mb: &'a Mailbox) -> Self {
+        PeriphRegs {
+            mb,
+            writable: false,
+        }
+    }
+
+    /// Opt in to register writes
+    ///
+    /// Writing a wrong value may hang or damage the hardware attached to the board.
+    pub fn writable(mb: &'a Mailbox) -> Self {
+        PeriphRegs { mb, writable: true }
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    pub fn read(&self, reg: RegisterId) -> Result<u32> {
+        get_periph_reg(self.mb, reg)
+    }
+
+    /// Write `value` and return the value reported back by the firmware
+    pub fn write(&self, reg: RegisterId, value: u32) -> Result<u32> {
+        if !self.writable {
+            return Err(Error::PeriphWriteNotAllowed { reg: reg.0 });
+        }
+        set_periph_reg(self.mb, reg, value)
+    }
+
+    /// Read, apply `f` and write back unless the value is unchanged
+    pub fn modify<F>(&self, reg: RegisterId, f: F) -> Result<u32>
+    where
+        F: FnOnce(u32) -> u32,
+    {
+        if !self.writable {
+            return Err(Error::PeriphWriteNotAllowed { reg: reg.0 });
+        }
+        let value = self.read(reg)?;
+        match f(value) {
+            new if new == value => Ok(value),
+            new => self.write(reg, new),
+        }
+    }
+
+    pub fn set_bits(&self, reg: RegisterId, mask: u32) -> Result<u32> {
+        self.modify(reg, |v| v | mask)
+    }
+
+    pub fn clear_bits(&self, reg: RegisterId, mask: u32) -> Result<u32> {
+        self.modify(reg, |v| v & !mask)
+    }
+
+    /// Replace the bits of `mask` with `value` shifted to the lowest bit of `mask`
+    pub fn write_field(&self, reg: RegisterId, mask: u32, value: u32) -> Result<u32> {
+        self.modify(reg, |v| replace_field(v, mask, value))
+    }
+
+    /// Bits of `mask` shifted down to bit 0
+    pub fn read_field(&self, reg: RegisterId, mask: u32) -> Result<u32> {
+        self.read(reg)
+            .map(|v| (v & mask) >> mask.trailing_zeros().min(31))
+    }
+}
+
+fn replace_field(reg: u32, mask: u32, value: u32) -> u32 {
+    let shift = mask.trailing_zeros().min(31);
+    (reg & !mask) | ((value << shift) & mask)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn field() {
+        assert_eq!(replace_field(0xffff_ffff, 0x0000_0f00, 0x5), 0xffff_f5ff);
+        assert_eq!(replace_field(0, 0x0000_0f00, 0x15), 0x0000_0500);
+        assert_eq!(replace_field(0x1234, 0, 0xff), 0x1234);
+    }
+}
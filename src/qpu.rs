@@ -0,0 +1,319 @@
+//! QPU jobs of the VideoCore IV (Pi 0 to 3)
+//!
+//! The QPUs are powered by `QpuEnable`, and `JobBuilder` places the programs
+//! and the uniform streams in locked VideoCore memory before submitting them.
+//!
+
+use std::mem::size_of;
+use std::time::Duration;
+
+use log::*;
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::memflag;
+use crate::memory::LockedMemory;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Number of QPUs of the VideoCore IV
+pub const NUM_QPUS: usize = 12;
+
+/// Power the QPUs on or off
+pub fn set_enable_qpu(mb: &Mailbox, enable: bool) -> Result<()> {
+    use message::enable_qpu::*;
+
+    let mut msg = Message {
+        in_: In {
+            enable: enable as u32,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_SET_ENABLE_QPU,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    match unsafe { msg.out.status } {
+        0 => Ok(()),
+        status => Err(Error::QpuEnableFailed { status }),
+    }
+}
+
+/// Run `num_qpus` jobs described by the control list at bus address `control`
+///
+/// The control list holds a pair of bus addresses, uniforms then code, per job.
+/// The firmware reports a nonzero status, returned as `Error::QpuTimeout`,
+/// when the jobs have not all completed within `timeout`.
+///
+/// # Safety
+///
+/// The control list, the programs and the memory they access must stay valid
+/// until the jobs complete.
+pub unsafe fn execute_qpu(
+    mb: &Mailbox,
+    num_qpus: u32,
+    control: u32,
+    noflush: bool,
+    timeout: Duration,
+) -> Result<()> {
+    use message::execute_qpu::*;
+
+    let mut msg = Message {
+        in_: In {
+            num_qpus,
+            control,
+            noflush: noflush as u32,
+            timeout: timeout.as_millis().min(u32::MAX as u128) as u32,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_EXECUTE_QPU,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    match msg.out.status {
+        0 => Ok(()),
+        status => Err(Error::QpuTimeout { status }),
+    }
+}
+
+/// Keeps the QPUs powered while it lives
+#[derive(Debug)]
+pub struct QpuEnable<'a> {
+    mb: &'a Mailbox,
+}
+
+impl<'a> QpuEnable<'a> {
+    pub fn new(mb: &'a Mailbox) -> Result<Self> {
+        set_enable_qpu(mb, true)?;
+        Ok(QpuEnable { mb })
+    }
+
+    pub fn mailbox(&self) -> &'a Mailbox {
+        self.mb
+    }
+}
+
+impl<'a> Drop for QpuEnable<'a> {
+    fn drop(&mut self) {
+        if let Err(err) = set_enable_qpu(self.mb, false) {
+            error!("failed to disable QPU: {}", err);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Job {
+    code: Vec<u64>,
+    uniforms: Vec<u32>,
+}
+
+/// Builder of a submission of up to `NUM_QPUS` jobs
+#[derive(Debug, Clone)]
+pub struct JobBuilder {
+    jobs: Vec<Job>,
+    noflush: bool,
+    timeout: Duration,
+}
+
+impl Default for JobBuilder {
+    fn default() -> Self {
+        JobBuilder {
+            jobs: Vec::new(),
+            noflush: false,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl JobBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a job running `code`, 64 bits instructions, with `uniforms`
+    pub fn job(&mut self, code: &[u64], uniforms: &[u32]) -> &mut Self {
+        self.jobs.push(Job {
+            code: code.to_vec(),
+            uniforms: uniforms.to_vec(),
+        });
+        self
+    }
+
+    /// Skip flushing the L2 cache before running the jobs
+    pub fn noflush(&mut self, noflush: bool) -> &mut Self {
+        self.noflush = noflush;
+        self
+    }
+
+    /// Time to wait for the jobs, 10 seconds by default
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Check the number of jobs and that each job has code
+    fn check(&self) -> Result<()> {
+        if self.jobs.is_empty() || NUM_QPUS < self.jobs.len() {
+            return Err(Error::InvalidQpuJobs {
+                count: self.jobs.len(),
+            });
+        }
+        match self.jobs.iter().position(|job| job.code.is_empty()) {
+            Some(job) => Err(Error::EmptyQpuJob { job }),
+            None => Ok(()),
+        }
+    }
+
+    /// Bytes of the control list, the programs and the uniforms
+    fn layout_size(&self) -> Result<u32> {
+        self.jobs
+            .iter()
+            .try_fold(0u32, |size, job| {
+                let code = u32::try_from(job.code.len()).ok()?.checked_mul(8)?;
+                let uniforms = u32::try_from(job.uniforms.len()).ok()?.checked_mul(4)?;
+                size.checked_add(8)?
+                    .checked_add(code)?
+                    .checked_add(uniforms)
+            })
+            .ok_or(Error::QpuJobsTooLarge)
+    }
+
+    /// Write the jobs into `buf` located at bus address `busaddr`
+    ///
+    /// Programs precede the uniforms to keep the instructions 64 bits aligned.
+    /// `buf` must be `layout_size` bytes long.
+    fn layout(&self, buf: &mut [u8], busaddr: u32) -> Result<()> {
+        let address = |offset: usize| {
+            u32::try_from(offset)
+                .ok()
+                .and_then(|offset| busaddr.checked_add(offset))
+                .ok_or(Error::QpuJobsTooLarge)
+        };
+        let mut put = |offset: &mut usize, word: u32| {
+            buf[*offset..*offset + 4].copy_from_slice(&word.to_le_bytes());
+            *offset += 4;
+        };
+        let mut control = 0;
+        let mut code = self.jobs.len() * 8;
+        let mut uniforms = code
+            + self
+                .jobs
+                .iter()
+                .map(|job| job.code.len() * 8)
+                .sum::<usize>();
+        for job in &self.jobs {
+            put(&mut control, address(uniforms)?);
+            put(&mut control, address(code)?);
+            for &inst in &job.code {
+                put(&mut code, inst as u32);
+                put(&mut code, (inst >> 32) as u32);
+            }
+            for &uniform in &job.uniforms {
+                put(&mut uniforms, uniform);
+            }
+        }
+        Ok(())
+    }
+
+    /// Upload the jobs and run them until completion
+    ///
+    /// # Safety
+    ///
+    /// The programs must be valid and only access memory through the uniforms.
+    pub unsafe fn submit(&self, qpu: &QpuEnable) -> Result<()> {
+        self.check()?;
+        let mb = qpu.mailbox();
+        let size = self.layout_size()?;
+        let mem = LockedMemory::new(mb, size, 4096, memflag::Flags::MEM_FLAG_L1_NONALLOCATING)?;
+        self.layout(
+            &mut mem.map()?.as_mut_slice()[..size as usize],
+            mem.bus_address(),
+        )?;
+        execute_qpu(
+            mb,
+            self.jobs.len() as u32,
+            mem.bus_address(),
+            self.noflush,
+            self.timeout,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layout_jobs() {
+        let mut builder = JobBuilder::new();
+        builder
+            .job(&[0x1111_2222_3333_4444], &[7, 8])
+            .job(&[0x5555_6666_7777_8888], &[9]);
+        let size = builder.layout_size().unwrap();
+        assert_eq!(size, 16 + 8 + 8 + 8 + 4);
+
+        let mut buf = vec![0u8; size as usize];
+        builder.layout(&mut buf, 0xc000_0000).unwrap();
+        let words: Vec<u32> = buf
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                0xc000_0020,
+                0xc000_0010,
+                0xc000_0028,
+                0xc000_0018,
+                0x3333_4444,
+                0x1111_2222,
+                0x7777_8888,
+                0x5555_6666,
+                7,
+                8,
+                9,
+            ]
+        );
+    }
+    #[test]
+    fn layout_address_overflow() {
+        let mut builder = JobBuilder::new();
+        builder.job(&[0x1111_2222_3333_4444], &[7, 8]);
+        let mut buf = vec![0u8; builder.layout_size().unwrap() as usize];
+        assert!(matches!(
+            builder.layout(&mut buf, 0xffff_fff0),
+            Err(Error::QpuJobsTooLarge)
+        ));
+    }
+
+    #[test]
+    fn check_jobs() {
+        let mut builder = JobBuilder::new();
+        assert!(matches!(
+            builder.check(),
+            Err(Error::InvalidQpuJobs { count: 0 })
+        ));
+        builder.job(&[0x1111_2222_3333_4444], &[]);
+        assert!(builder.check().is_ok());
+        builder.job(&[], &[1]);
+        assert!(matches!(
+            builder.check(),
+            Err(Error::EmptyQpuJob { job: 1 })
+        ));
+
+        let mut builder = JobBuilder::new();
+        for _ in 0..=NUM_QPUS {
+            builder.job(&[0], &[]);
+        }
+        assert!(matches!(
+            builder.check(),
+            Err(Error::InvalidQpuJobs { count: 13 })
+        ));
+    }
+}
@@ -61,6 +61,7 @@ pub enum rpi_firmware_property_tag {
     RPI_FIRMWARE_SET_ENABLE_QPU = 0x00030012,
     RPI_FIRMWARE_GET_DISPMANX_RESOURCE_MEM_HANDLE = 0x00030014,
     RPI_FIRMWARE_GET_EDID_BLOCK = 0x00030020,
+    RPI_FIRMWARE_GET_EDID_BLOCK_DISPLAY = 0x00030023,
     RPI_FIRMWARE_GET_CUSTOMER_OTP = 0x00030021,
     RPI_FIRMWARE_GET_DOMAIN_STATE = 0x00030030,
     RPI_FIRMWARE_GET_THROTTLED = 0x00030046,
@@ -77,6 +78,8 @@ pub enum rpi_firmware_property_tag {
     RPI_FIRMWARE_SET_GPIO_CONFIG = 0x00038043,
     RPI_FIRMWARE_GET_PERIPH_REG = 0x00030045,
     RPI_FIRMWARE_SET_PERIPH_REG = 0x00038045,
+    RPI_FIRMWARE_GET_PRIVATE_KEY = 0x00030081,
+    RPI_FIRMWARE_SET_PRIVATE_KEY = 0x00038081,
 
     /* Dispmanx TAGS */
     RPI_FIRMWARE_FRAMEBUFFER_ALLOCATE = 0x00040001,
@@ -92,6 +95,9 @@ pub enum rpi_firmware_property_tag {
     RPI_FIRMWARE_FRAMEBUFFER_GET_PALETTE = 0x0004000b,
     RPI_FIRMWARE_FRAMEBUFFER_GET_TOUCHBUF = 0x0004000f,
     RPI_FIRMWARE_FRAMEBUFFER_GET_GPIOVIRTBUF = 0x00040010,
+    RPI_FIRMWARE_FRAMEBUFFER_GET_NUM_DISPLAYS = 0x00040013,
+    RPI_FIRMWARE_FRAMEBUFFER_GET_DISPLAY_SETTINGS = 0x00040014,
+    RPI_FIRMWARE_FRAMEBUFFER_GET_DISPLAY_ID = 0x00040016,
     RPI_FIRMWARE_FRAMEBUFFER_RELEASE = 0x00048001,
     RPI_FIRMWARE_FRAMEBUFFER_TEST_PHYSICAL_WIDTH_HEIGHT = 0x00044003,
     RPI_FIRMWARE_FRAMEBUFFER_TEST_VIRTUAL_WIDTH_HEIGHT = 0x00044004,
@@ -114,6 +120,7 @@ pub enum rpi_firmware_property_tag {
     RPI_FIRMWARE_FRAMEBUFFER_SET_GPIOVIRTBUF = 0x00048020,
     RPI_FIRMWARE_FRAMEBUFFER_SET_VSYNC = 0x0004800e,
     RPI_FIRMWARE_FRAMEBUFFER_SET_BACKLIGHT = 0x0004800f,
+    RPI_FIRMWARE_FRAMEBUFFER_SET_DISPLAY_NUM = 0x00048013,
 
     RPI_FIRMWARE_VCHIQ_INIT = 0x00048010,
 
@@ -121,6 +128,29 @@ pub enum rpi_firmware_property_tag {
     RPI_FIRMWARE_GET_DMA_CHANNELS = 0x00060001,
 }
 
+/// Clock ids of the firmware
+///
+/// Derived from the rpi-5.10.y version of the header.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(non_camel_case_types)]
+pub enum rpi_firmware_clk_id {
+    RPI_FIRMWARE_EMMC_CLK_ID = 1,
+    RPI_FIRMWARE_UART_CLK_ID = 2,
+    RPI_FIRMWARE_ARM_CLK_ID = 3,
+    RPI_FIRMWARE_CORE_CLK_ID = 4,
+    RPI_FIRMWARE_V3D_CLK_ID = 5,
+    RPI_FIRMWARE_H264_CLK_ID = 6,
+    RPI_FIRMWARE_ISP_CLK_ID = 7,
+    RPI_FIRMWARE_SDRAM_CLK_ID = 8,
+    RPI_FIRMWARE_PIXEL_CLK_ID = 9,
+    RPI_FIRMWARE_PWM_CLK_ID = 10,
+    RPI_FIRMWARE_HEVC_CLK_ID = 11,
+    RPI_FIRMWARE_EMMC2_CLK_ID = 12,
+    RPI_FIRMWARE_M2MC_CLK_ID = 13,
+    RPI_FIRMWARE_PIXEL_BVB_CLK_ID = 14,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
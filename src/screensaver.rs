@@ -0,0 +1,227 @@
+//! Blank the screen after a period of inactivity
+//!
+//! The firmware is abstracted by `Backend`, so the screen saver can be driven
+//! deterministically.
+//!
+
+use std::time::{Duration, Instant};
+
+use log::*;
+
+use crate::error::Result;
+use crate::framebuffer;
+use crate::mailbox::Mailbox;
+
+/// Access to the firmware used by `ScreenSaver`
+pub trait Backend {
+    fn blank_screen(&self, blank: bool) -> Result<bool>;
+    fn set_backlight(&self, level: u32) -> Result<u32>;
+}
+
+impl Backend for Mailbox {
+    fn blank_screen(&self, blank: bool) -> Result<bool> {
+        framebuffer::blank_screen(self, blank)
+    }
+
+    fn set_backlight(&self, level: u32) -> Result<u32> {
+        framebuffer::set_backlight(self, level)
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for &B {
+    fn blank_screen(&self, blank: bool) -> Result<bool> {
+        (**self).blank_screen(blank)
+    }
+
+    fn set_backlight(&self, level: u32) -> Result<u32> {
+        (**self).set_backlight(level)
+    }
+}
+
+/// Blanks the screen and turns the backlight off when idle for `idle`
+///
+/// The firmware cannot report the backlight level, so the level to restore
+/// is the one given to `new` or `set_backlight`.
+/// The screen is restored on drop.
+#[derive(Debug)]
+pub struct ScreenSaver<'a, B: Backend + ?Sized = Mailbox> {
+    backend: &'a B,
+    idle: Duration,
+    backlight: Option<u32>,
+    last_activity: Instant,
+    blanked: bool,
+}
+
+impl<'a, B: Backend + ?Sized> ScreenSaver<'a, B> {
+    /// `backlight`: the current level, or `None` to leave the backlight alone
+    pub fn new(backend: &'a B, idle: Duration, backlight: Option<u32>) -> Self {
+        ScreenSaver {
+            backend,
+            idle,
+            backlight,
+            last_activity: Instant::now(),
+            blanked: false,
+        }
+    }
+
+    pub fn is_blanked(&self) -> bool {
+        self.blanked
+    }
+
+    pub fn idle(&self) -> Duration {
+        self.idle
+    }
+
+    /// Record user activity now, restoring the screen if blanked
+    pub fn activity(&mut self) -> Result<()> {
+        self.activity_at(Instant::now())
+    }
+
+    /// Record user activity at `now`, restoring the screen if blanked
+    pub fn activity_at(&mut self, now: Instant) -> Result<()> {
+        self.last_activity = now;
+        if self.blanked {
+            self.restore()?;
+        }
+        Ok(())
+    }
+
+    /// Blank the screen if idle long enough
+    ///
+    /// Returns whether the screen is blanked.
+    pub fn poll(&mut self) -> Result<bool> {
+        self.poll_at(Instant::now())
+    }
+
+    /// `poll` at `now`
+    ///
+    /// If blanking fails, the backlight is turned back on.
+    pub fn poll_at(&mut self, now: Instant) -> Result<bool> {
+        if !self.blanked && self.idle <= now.saturating_duration_since(self.last_activity) {
+            if self.backlight.is_some() {
+                self.backend.set_backlight(0)?;
+            }
+            if let Err(err) = self.backend.blank_screen(true) {
+                if let Some(level) = self.backlight {
+                    if let Err(err) = self.backend.set_backlight(level) {
+                        error!("failed to restore the backlight: {}", err);
+                    }
+                }
+                return Err(err);
+            }
+            self.blanked = true;
+        }
+        Ok(self.blanked)
+    }
+
+    /// Change the backlight level, which is restored after blanking
+    ///
+    /// While blanked, the level is only recorded.
+    pub fn set_backlight(&mut self, level: u32) -> Result<u32> {
+        self.backlight = Some(level);
+        if self.blanked {
+            return Ok(level);
+        }
+        self.backend.set_backlight(level)
+    }
+
+    /// Unblank the screen and restore the backlight
+    ///
+    /// The screen stays blanked until both succeed, so that the next activity retries.
+    fn restore(&mut self) -> Result<()> {
+        self.backend.blank_screen(false)?;
+        if let Some(level) = self.backlight {
+            self.backend.set_backlight(level)?;
+        }
+        self.blanked = false;
+        Ok(())
+    }
+}
+
+impl<'a, B: Backend + ?Sized> Drop for ScreenSaver<'a, B> {
+    fn drop(&mut self) {
+        if self.blanked {
+            if let Err(err) = self.restore() {
+                error!("failed to restore the screen: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+
+    use crate::error::Error;
+
+    #[derive(Default)]
+    struct FakeBackend {
+        blanked: Cell<bool>,
+        backlight: Cell<u32>,
+        fail_blank: Cell<bool>,
+    }
+
+    impl Backend for FakeBackend {
+        fn blank_screen(&self, blank: bool) -> Result<bool> {
+            if self.fail_blank.get() {
+                return Err(Error::RequestFailed { code: 0x8000_0001 });
+            }
+            self.blanked.set(blank);
+            Ok(blank)
+        }
+        fn set_backlight(&self, level: u32) -> Result<u32> {
+            self.backlight.set(level);
+            Ok(level)
+        }
+    }
+
+    #[test]
+    fn blanks_when_idle_and_restores() {
+        let backend = FakeBackend::default();
+        backend.backlight.set(200);
+        let start = Instant::now();
+        let mut saver = ScreenSaver::new(&backend, Duration::from_secs(60), Some(200));
+        saver.activity_at(start).unwrap();
+
+        assert!(!saver.poll_at(start + Duration::from_secs(59)).unwrap());
+        assert!(saver.poll_at(start + Duration::from_secs(60)).unwrap());
+        assert!(backend.blanked.get());
+        assert_eq!(backend.backlight.get(), 0);
+
+        // the level set while blanked is applied on restore
+        saver.set_backlight(100).unwrap();
+        assert_eq!(backend.backlight.get(), 0);
+        saver.activity_at(start + Duration::from_secs(61)).unwrap();
+        assert!(!saver.is_blanked());
+        assert!(!backend.blanked.get());
+        assert_eq!(backend.backlight.get(), 100);
+    }
+
+    #[test]
+    fn failed_blank_restores_backlight() {
+        let backend = FakeBackend::default();
+        backend.backlight.set(200);
+        backend.fail_blank.set(true);
+        let start = Instant::now();
+        let mut saver = ScreenSaver::new(&backend, Duration::from_secs(60), Some(200));
+        saver.activity_at(start).unwrap();
+
+        assert!(saver.poll_at(start + Duration::from_secs(60)).is_err());
+        assert!(!saver.is_blanked());
+        assert_eq!(backend.backlight.get(), 200);
+    }
+
+    #[test]
+    fn drop_restores() {
+        let backend = FakeBackend::default();
+        let start = Instant::now();
+        {
+            let mut saver = ScreenSaver::new(&backend, Duration::from_secs(1), None);
+            saver.activity_at(start).unwrap();
+            assert!(saver.poll_at(start + Duration::from_secs(1)).unwrap());
+            assert!(backend.blanked.get());
+        }
+        assert!(!backend.blanked.get());
+    }
+}
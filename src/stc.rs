@@ -0,0 +1,209 @@
+//! VideoCore system timer
+//!
+//! The STC is a free running 64 bits counter at 1 MHz which the VideoCore uses
+//! for timestamps, e.g. of camera frames. `Correlation` converts them from and
+//! to `CLOCK_MONOTONIC` of the host.
+//!
+
+use std::mem::size_of;
+use std::thread;
+use std::time::Duration;
+
+use nix::time::{clock_gettime, ClockId};
+
+use crate::error::Result;
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Read the STC in microseconds
+pub fn get_stc(mb: &Mailbox) -> Result<u64> {
+    use message::stc::*;
+
+    let mut msg = Message { in_: In };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_GET_STC,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok((msg.out.stc_hi as u64) << 32 | msg.out.stc_lo as u64) }
+}
+
+/// `CLOCK_MONOTONIC` in nanoseconds
+pub fn monotonic_now() -> Result<u64> {
+    let ts = clock_gettime(ClockId::CLOCK_MONOTONIC)?;
+    Ok(ts.tv_sec() as u64 * 1_000_000_000 + ts.tv_nsec() as u64)
+}
+
+/// STC read at a host time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// Middle of the request in `CLOCK_MONOTONIC` nanoseconds
+    pub host_ns: u64,
+    /// STC in microseconds
+    pub stc: u64,
+    /// Duration of the request in nanoseconds, the uncertainty of `host_ns`
+    pub round_trip_ns: u64,
+}
+
+impl Sample {
+    pub fn read(mb: &Mailbox) -> Result<Self> {
+        let before = monotonic_now()?;
+        let stc = get_stc(mb)?;
+        let after = monotonic_now()?;
+        Ok(Sample {
+            host_ns: before + (after - before) / 2,
+            stc,
+            round_trip_ns: after - before,
+        })
+    }
+}
+
+/// Linear relation between the STC and `CLOCK_MONOTONIC`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Correlation {
+    host_ns: u64,
+    stc_ns: u64,
+    /// STC nanoseconds per host nanosecond
+    rate: f64,
+}
+
+impl Correlation {
+    /// Least squares fit of `samples`
+    ///
+    /// A single sample gives the offset only, assuming no drift.
+    /// Returns `None` if `samples` is empty.
+    pub fn fit(samples: &[Sample]) -> Option<Self> {
+        let first = samples.first()?;
+        let host_ref = first.host_ns;
+        let stc_ref = first.stc * 1000;
+        let n = samples.len() as f64;
+        let points = || {
+            samples.iter().map(move |s| {
+                (
+                    (s.host_ns as i64 - host_ref as i64) as f64,
+                    (s.stc as i64 * 1000 - stc_ref as i64) as f64,
+                )
+            })
+        };
+        let mean_x = points().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points().map(|(_, y)| y).sum::<f64>() / n;
+        let sxx: f64 = points().map(|(x, _)| (x - mean_x) * (x - mean_x)).sum();
+        let sxy: f64 = points().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let rate = if sxx == 0.0 { 1.0 } else { sxy / sxx };
+        Some(Correlation {
+            host_ns: (host_ref as i64 + mean_x.round() as i64) as u64,
+            stc_ns: (stc_ref as i64 + mean_y.round() as i64) as u64,
+            rate,
+        })
+    }
+
+    /// Host time minus STC time in nanoseconds at the reference point
+    pub fn offset_ns(&self) -> i64 {
+        self.host_ns as i64 - self.stc_ns as i64
+    }
+
+    /// Drift of the STC against the host in parts per million
+    pub fn drift_ppm(&self) -> f64 {
+        (self.rate - 1.0) * 1e6
+    }
+
+    /// Convert an STC timestamp in microseconds to `CLOCK_MONOTONIC` nanoseconds
+    ///
+    /// Returns `None` if the time falls before the start of `CLOCK_MONOTONIC`
+    /// or does not fit in a `u64`.
+    pub fn to_host_ns(&self, stc: u64) -> Option<u64> {
+        let d = (i128::from(stc) * 1000 - i128::from(self.stc_ns)) as f64 / self.rate;
+        u64::try_from(i128::from(self.host_ns) + d.round() as i128).ok()
+    }
+
+    /// Convert `CLOCK_MONOTONIC` nanoseconds to an STC timestamp in microseconds
+    ///
+    /// Returns `None` if the time falls before the STC started counting
+    /// or does not fit in a `u64`.
+    pub fn to_stc(&self, host_ns: u64) -> Option<u64> {
+        let d = (i128::from(host_ns) - i128::from(self.host_ns)) as f64 * self.rate;
+        u64::try_from(i128::from(self.stc_ns) + d.round() as i128)
+            .ok()
+            .map(|ns| ns / 1000)
+    }
+}
+
+/// Sample the STC `count` times every `interval` and fit a `Correlation`
+///
+/// Samples whose round trip is over twice the fastest one are discarded as
+/// the request was probably preempted.
+pub fn correlate(mb: &Mailbox, count: usize, interval: Duration) -> Result<Correlation> {
+    let mut samples = Vec::with_capacity(count.max(1));
+    for i in 0..count.max(1) {
+        if i != 0 {
+            thread::sleep(interval);
+        }
+        samples.push(Sample::read(mb)?);
+    }
+    let fastest = samples.iter().map(|s| s.round_trip_ns).min().unwrap_or(0);
+    samples.retain(|s| s.round_trip_ns <= fastest.max(1) * 2);
+    Ok(Correlation::fit(&samples).unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fit_drift() {
+        // STC 100 ppm fast and 5 s behind the host
+        let samples: Vec<_> = (0..10u64)
+            .map(|i| {
+                let host_ns = 10_000_000_000 + i * 100_000_000;
+                let elapsed_us = i * 100_000;
+                Sample {
+                    host_ns,
+                    stc: 5_000_000 + elapsed_us + elapsed_us / 10_000,
+                    round_trip_ns: 1000,
+                }
+            })
+            .collect();
+        let c = Correlation::fit(&samples).unwrap();
+        assert!((c.drift_ppm() - 100.0).abs() < 1.0);
+        assert_eq!(c.to_host_ns(5_000_000), Some(10_000_000_000));
+        assert_eq!(c.to_stc(10_000_000_000), Some(5_000_000));
+        assert_eq!(c.to_stc(c.to_host_ns(5_500_050).unwrap()), Some(5_500_050));
+        assert!((c.offset_ns() - 5_000_000_000).abs() < 1_000_000);
+
+        let single = Correlation::fit(&samples[..1]).unwrap();
+        assert_eq!(single.drift_ppm(), 0.0);
+        assert_eq!(single.offset_ns(), 5_000_000_000);
+        assert!(Correlation::fit(&[]).is_none());
+    }
+
+    #[test]
+    fn before_reference() {
+        // STC 5 s behind the host, reference at 10 s of host time
+        let c = Correlation {
+            host_ns: 10_000_000_000,
+            stc_ns: 5_000_000_000,
+            rate: 1.0,
+        };
+        assert_eq!(c.to_host_ns(1_000_000), Some(6_000_000_000));
+        assert_eq!(c.to_stc(6_000_000_000), Some(1_000_000));
+        assert_eq!(c.to_host_ns(0), Some(5_000_000_000));
+
+        // before the STC started
+        assert_eq!(c.to_stc(4_000_000_000), None);
+        assert_eq!(c.to_stc(0), None);
+
+        // host 5 s behind the STC: early STC times are before CLOCK_MONOTONIC started
+        let c = Correlation {
+            host_ns: 5_000_000_000,
+            stc_ns: 10_000_000_000,
+            rate: 1.0,
+        };
+        assert_eq!(c.to_host_ns(1_000_000), None);
+        assert_eq!(c.to_host_ns(u64::MAX), None);
+        assert_eq!(c.to_stc(0), Some(5_000_000));
+    }
+}
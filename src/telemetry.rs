@@ -0,0 +1,518 @@
+//! Periodic sampling of firmware properties
+//!
+//! `Sampler` polls a set of properties at a fixed interval on a background
+//! thread, keeps a bounded history of the readings and notifies registered
+//! callbacks when a reading crosses a threshold or the throttled bits change.
+//!
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use log::*;
+
+use crate::error::Result;
+use crate::mailbox::Mailbox;
+use crate::raspberrypi_firmware::rpi_firmware_clk_id;
+use crate::throttled;
+
+/// A property polled by `Sampler`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Property {
+    /// `get_temperature`
+    Temperature,
+    /// `get_throttled`
+    Throttled,
+    /// `get_clock_rate` of the clock id
+    ClockRate(rpi_firmware_clk_id),
+    /// `get_voltage` of the voltage id
+    Voltage(u32),
+}
+
+impl Property {
+    /// Read the current value of the property
+    pub fn read(&self, mb: &Mailbox) -> Result<u32> {
+        match *self {
+            Property::Temperature => crate::get_temperature(mb),
+            Property::Throttled => crate::get_throttled(mb),
+            Property::ClockRate(clock_id) => crate::get_clock_rate(mb, clock_id as u32),
+            Property::Voltage(voltage_id) => crate::get_voltage(mb, voltage_id),
+        }
+    }
+}
+
+/// Readings taken at a point in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub timestamp: Instant,
+    /// Values of the properties which could be read
+    pub values: Vec<(Property, u32)>,
+}
+
+impl Sample {
+    pub fn get(&self, property: Property) -> Option<u32> {
+        self.values
+            .iter()
+            .find(|(p, _)| *p == property)
+            .map(|&(_, v)| v)
+    }
+}
+
+/// Summary of the values of a property over a window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub min: u32,
+    pub max: u32,
+    pub mean: f64,
+    pub count: usize,
+}
+
+/// Ring buffer of samples
+///
+/// The oldest sample is discarded when a sample is pushed to a full history.
+#[derive(Debug, Clone)]
+pub struct History {
+    capacity: usize,
+    samples: VecDeque<Sample>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, sample: Sample) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Iterate samples from the oldest one
+    pub fn iter(&self) -> impl Iterator<Item = &Sample> {
+        self.samples.iter()
+    }
+
+    pub fn latest(&self) -> Option<&Sample> {
+        self.samples.back()
+    }
+
+    /// Statistics of `property` over the samples taken within `window` before the latest one
+    pub fn stats(&self, property: Property, window: Duration) -> Option<Stats> {
+        let latest = self.latest()?.timestamp;
+        let mut stats: Option<Stats> = None;
+        let mut sum = 0f64;
+        for sample in self.samples.iter().rev() {
+            if latest.duration_since(sample.timestamp) > window {
+                break;
+            }
+            let value = match sample.get(property) {
+                Some(value) => value,
+                None => continue,
+            };
+            sum += value as f64;
+            stats = Some(match stats {
+                None => Stats {
+                    min: value,
+                    max: value,
+                    mean: 0.0,
+                    count: 1,
+                },
+                Some(s) => Stats {
+                    min: s.min.min(value),
+                    max: s.max.max(value),
+                    mean: 0.0,
+                    count: s.count + 1,
+                },
+            });
+        }
+        stats.map(|s| Stats {
+            mean: sum / s.count as f64,
+            ..s
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Rising,
+    Falling,
+}
+
+/// Notification passed to the callbacks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The value of `property` crossed `threshold`
+    Threshold {
+        property: Property,
+        threshold: u32,
+        value: u32,
+        direction: Direction,
+        timestamp: Instant,
+    },
+    /// Some of the watched throttled bits changed
+    Throttled {
+        previous: throttled::Flags,
+        current: throttled::Flags,
+        timestamp: Instant,
+    },
+}
+
+type Callback = Box<dyn FnMut(&Event) + Send>;
+
+enum Trigger {
+    Threshold {
+        property: Property,
+        threshold: u32,
+        above: bool,
+    },
+    Throttled {
+        mask: throttled::Flags,
+        last: throttled::Flags,
+    },
+}
+
+struct Watch {
+    trigger: Trigger,
+    callback: Callback,
+}
+
+impl Watch {
+    fn observe(&mut self, sample: &Sample) {
+        match self.trigger {
+            Trigger::Threshold {
+                property,
+                threshold,
+                ref mut above,
+            } => {
+                let value = match sample.get(property) {
+                    Some(value) => value,
+                    None => return,
+                };
+                let now_above = threshold <= value;
+                if now_above == *above {
+                    return;
+                }
+                *above = now_above;
+                (self.callback)(&Event::Threshold {
+                    property,
+                    threshold,
+                    value,
+                    direction: if now_above {
+                        Direction::Rising
+                    } else {
+                        Direction::Falling
+                    },
+                    timestamp: sample.timestamp,
+                });
+            }
+            Trigger::Throttled { mask, ref mut last } => {
+                let current = match sample.get(Property::Throttled) {
+                    Some(value) => throttled::Flags::from_bits_retain(value) & mask,
+                    None => return,
+                };
+                if current == *last {
+                    return;
+                }
+                let previous = std::mem::replace(last, current);
+                (self.callback)(&Event::Throttled {
+                    previous,
+                    current,
+                    timestamp: sample.timestamp,
+                });
+            }
+        }
+    }
+}
+
+/// Builder of `Sampler`
+///
+/// Before the first sample, values are taken to be below every threshold
+/// and no throttled bits are taken to be set.
+pub struct SamplerBuilder {
+    interval: Duration,
+    capacity: usize,
+    properties: Vec<Property>,
+    watches: Vec<Watch>,
+}
+
+impl Default for SamplerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SamplerBuilder {
+    /// Sample nothing every second keeping 60 samples
+    pub fn new() -> Self {
+        SamplerBuilder {
+            interval: Duration::from_secs(1),
+            capacity: 60,
+            properties: Vec::new(),
+            watches: Vec::new(),
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Number of samples kept in the history
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn property(mut self, property: Property) -> Self {
+        if !self.properties.contains(&property) {
+            self.properties.push(property);
+        }
+        self
+    }
+
+    /// Call `callback` each time the value of `property` crosses `threshold`
+    ///
+    /// `property` is sampled even if it was not added with `property`.
+    pub fn on_threshold<F>(self, property: Property, threshold: u32, callback: F) -> Self
+    where
+        F: FnMut(&Event) + Send + 'static,
+    {
+        let mut this = self.property(property);
+        this.watches.push(Watch {
+            trigger: Trigger::Threshold {
+                property,
+                threshold,
+                above: false,
+            },
+            callback: Box::new(callback),
+        });
+        this
+    }
+
+    /// Call `callback` each time any bit of `mask` in the throttled state changes
+    pub fn on_throttled<F>(self, mask: throttled::Flags, callback: F) -> Self
+    where
+        F: FnMut(&Event) + Send + 'static,
+    {
+        let mut this = self.property(Property::Throttled);
+        this.watches.push(Watch {
+            trigger: Trigger::Throttled {
+                mask,
+                last: throttled::Flags::empty(),
+            },
+            callback: Box::new(callback),
+        });
+        this
+    }
+
+    /// Start sampling on a background thread
+    ///
+    /// The first sample is taken immediately.
+    /// Callbacks are called on the background thread.
+    pub fn spawn(self, mb: Arc<Mailbox>) -> Sampler {
+        let history = Arc::new(Mutex::new(History::new(self.capacity)));
+        let (stop, stopped) = mpsc::channel::<()>();
+        let SamplerBuilder {
+            interval,
+            properties,
+            mut watches,
+            ..
+        } = self;
+
+        let shared = history.clone();
+        let handle = thread::spawn(move || loop {
+            let timestamp = Instant::now();
+            let values = properties
+                .iter()
+                .filter_map(|property| match property.read(&mb) {
+                    Ok(value) => Some((*property, value)),
+                    Err(err) => {
+                        warn!("failed to read {:?}: {}", property, err);
+                        None
+                    }
+                })
+                .collect();
+            let sample = Sample { timestamp, values };
+            for watch in watches.iter_mut() {
+                watch.observe(&sample);
+            }
+            shared.lock().unwrap().push(sample);
+
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                _ => break,
+            }
+        });
+
+        Sampler {
+            history,
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+
+/// Background sampler of firmware properties
+///
+/// Sampling stops when the sampler is dropped.
+pub struct Sampler {
+    history: Arc<Mutex<History>>,
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Sampler {
+    /// Copy of the current history
+    pub fn history(&self) -> History {
+        self.history.lock().unwrap().clone()
+    }
+
+    pub fn latest(&self) -> Option<Sample> {
+        self.history.lock().unwrap().latest().cloned()
+    }
+
+    /// See `History::stats`
+    pub fn stats(&self, property: Property, window: Duration) -> Option<Stats> {
+        self.history.lock().unwrap().stats(property, window)
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                error!("telemetry sampler thread panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn sample(base: Instant, secs: u64, values: Vec<(Property, u32)>) -> Sample {
+        Sample {
+            timestamp: base + Duration::from_secs(secs),
+            values,
+        }
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let base = Instant::now();
+        let mut history = History::new(3);
+        for i in 0..5 {
+            history.push(sample(base, i, vec![(Property::Temperature, i as u32)]));
+        }
+        assert_eq!(history.len(), 3);
+        let values: Vec<_> = history
+            .iter()
+            .map(|s| s.get(Property::Temperature).unwrap())
+            .collect();
+        assert_eq!(values, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn stats_within_window() {
+        let base = Instant::now();
+        let mut history = History::new(10);
+        history.push(sample(base, 0, vec![(Property::Temperature, 100)]));
+        history.push(sample(base, 1, vec![(Property::Temperature, 40)]));
+        history.push(sample(base, 2, vec![]));
+        history.push(sample(base, 3, vec![(Property::Temperature, 60)]));
+
+        let stats = history
+            .stats(Property::Temperature, Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(stats.min, 40);
+        assert_eq!(stats.max, 60);
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.mean, 50.0);
+        assert!(history
+            .stats(Property::Throttled, Duration::from_secs(2))
+            .is_none());
+    }
+
+    #[test]
+    fn threshold_and_throttled_transitions() {
+        let (tx, rx) = channel();
+        let tx2 = tx.clone();
+        let mut watches = [
+            Watch {
+                trigger: Trigger::Threshold {
+                    property: Property::Temperature,
+                    threshold: 70_000,
+                    above: false,
+                },
+                callback: Box::new(move |e: &Event| tx.send(*e).unwrap()),
+            },
+            Watch {
+                trigger: Trigger::Throttled {
+                    mask: throttled::Flags::THROTTLED,
+                    last: throttled::Flags::empty(),
+                },
+                callback: Box::new(move |e: &Event| tx2.send(*e).unwrap()),
+            },
+        ];
+
+        let base = Instant::now();
+        let readings = [(65_000, 0x0), (72_000, 0x4), (75_000, 0x5), (60_000, 0x1)];
+        for (i, &(temp, bits)) in readings.iter().enumerate() {
+            let s = sample(
+                base,
+                i as u64,
+                vec![(Property::Temperature, temp), (Property::Throttled, bits)],
+            );
+            for watch in watches.iter_mut() {
+                watch.observe(&s);
+            }
+        }
+
+        let events: Vec<_> = rx.try_iter().collect();
+        assert_eq!(events.len(), 4);
+        assert!(matches!(
+            events[0],
+            Event::Threshold {
+                value: 72_000,
+                direction: Direction::Rising,
+                ..
+            }
+        ));
+        assert!(
+            matches!(events[1], Event::Throttled { current, .. } if current == throttled::Flags::THROTTLED)
+        );
+        assert!(matches!(
+            events[2],
+            Event::Threshold {
+                value: 60_000,
+                direction: Direction::Falling,
+                ..
+            }
+        ));
+        assert!(matches!(events[3], Event::Throttled { current, .. } if current.is_empty()));
+    }
+}
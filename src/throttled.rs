@@ -0,0 +1,18 @@
+//! Bits of the value returned by `get_throttled`
+//!
+
+use bitflags::bitflags;
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Flags: u32 {
+        const UNDER_VOLTAGE = (1 << 0);
+        const ARM_FREQ_CAPPED = (1 << 1);
+        const THROTTLED = (1 << 2);
+        const SOFT_TEMP_LIMIT = (1 << 3);
+        const UNDER_VOLTAGE_OCCURRED = (1 << 16);
+        const ARM_FREQ_CAPPED_OCCURRED = (1 << 17);
+        const THROTTLED_OCCURRED = (1 << 18);
+        const SOFT_TEMP_LIMIT_OCCURRED = (1 << 19);
+    }
+}
@@ -0,0 +1,90 @@
+//! Initialisation of VCHIQ
+//!
+//! VCHIQ exchanges messages with the VideoCore through slots in shared memory.
+//! The ARM side allocates them and hands their bus address to the firmware;
+//! the protocol on top is left to higher level crates.
+//!
+
+use std::mem::size_of;
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::memflag;
+use crate::memory::LockedMemory;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Size of a VCHIQ slot
+pub const SLOT_SIZE: u32 = 4096;
+
+/// Hand the slot memory at `slot_busaddr` to the firmware
+///
+/// Returns the status reported by the firmware, 0 on success.
+pub fn vchiq_init(mb: &Mailbox, slot_busaddr: u32) -> Result<u32> {
+    use message::vchiq_init::*;
+
+    let mut msg = Message {
+        in_: In { slot_busaddr },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_VCHIQ_INIT,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    unsafe { Ok(msg.out.status) }
+}
+
+/// Slot memory for the firmware
+///
+/// Once initialised the firmware keeps using the slots; dropping this releases
+/// the memory and must happen only after VCHIQ is shut down.
+#[derive(Debug)]
+pub struct VchiqSlots<'a> {
+    mem: LockedMemory<'a>,
+}
+
+impl<'a> VchiqSlots<'a> {
+    /// Allocate `num_slots` zeroed slots
+    ///
+    /// Fails with `Error::InvalidSlotCount` if `num_slots` is 0 or too large.
+    ///
+    /// Fill in the slot zero header expected by the firmware through `map`,
+    /// then call `init`.
+    pub fn new(mb: &'a Mailbox, num_slots: u32) -> Result<Self> {
+        let size = match num_slots.checked_mul(SLOT_SIZE) {
+            Some(size) if num_slots != 0 => size,
+            _ => return Err(Error::InvalidSlotCount { num_slots }),
+        };
+        let mem = LockedMemory::new(
+            mb,
+            size,
+            SLOT_SIZE,
+            memflag::Flags::MEM_FLAG_L1_NONALLOCATING | memflag::Flags::MEM_FLAG_ZERO,
+        )?;
+        Ok(VchiqSlots { mem })
+    }
+
+    /// Hand the slots to the firmware
+    pub fn init(&self) -> Result<()> {
+        match vchiq_init(self.mem.mailbox(), self.mem.bus_address())? {
+            0 => Ok(()),
+            status => Err(Error::VchiqInitFailed { status }),
+        }
+    }
+
+    pub fn bus_address(&self) -> u32 {
+        self.mem.bus_address()
+    }
+
+    pub fn num_slots(&self) -> u32 {
+        self.mem.size() / SLOT_SIZE
+    }
+
+    /// Map the slots into the process
+    pub fn map(&self) -> Result<crate::mmap::MemoryMap> {
+        self.mem.map()
+    }
+}
@@ -0,0 +1,89 @@
+//! Execution of code on the VideoCore VPU
+//!
+//! The code runs with the privileges of the firmware; nothing protects the
+//! system from a faulty blob, hence every function of this module is `unsafe`.
+//!
+
+use std::mem::size_of;
+
+use crate::error::{Error, Result};
+use crate::kernel::rpi_firmware_property;
+use crate::mailbox::Mailbox;
+use crate::memflag;
+use crate::memory::LockedMemory;
+use crate::message;
+use crate::raspberrypi_firmware::rpi_firmware_property_tag::*;
+
+/// Arguments passed in the registers r0 to r5
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Args {
+    pub r0: u32,
+    pub r1: u32,
+    pub r2: u32,
+    pub r3: u32,
+    pub r4: u32,
+    pub r5: u32,
+}
+
+impl From<[u32; 6]> for Args {
+    fn from(r: [u32; 6]) -> Self {
+        Args {
+            r0: r[0],
+            r1: r[1],
+            r2: r[2],
+            r3: r[3],
+            r4: r[4],
+            r5: r[5],
+        }
+    }
+}
+
+/// Call the code at bus address `code` and return r0
+///
+/// # Safety
+///
+/// `code` must point to valid VPU code which returns.
+pub unsafe fn execute_code(mb: &Mailbox, code: u32, args: Args) -> Result<u32> {
+    use message::execute_code::*;
+
+    let mut msg = Message {
+        in_: In {
+            code,
+            r0: args.r0,
+            r1: args.r1,
+            r2: args.r2,
+            r3: args.r3,
+            r4: args.r4,
+            r5: args.r5,
+        },
+    };
+    rpi_firmware_property(
+        mb,
+        RPI_FIRMWARE_EXECUTE_CODE,
+        &mut msg as *mut Message as *mut u8,
+        size_of::<Message>(),
+        size_of::<Out>(),
+    )?;
+    Ok(msg.out.r0)
+}
+
+/// Copy `code` into locked VideoCore memory, call it and return r0
+///
+/// The memory holding the code is released before returning.
+///
+/// # Safety
+///
+/// `code` must be valid position independent VPU code which returns.
+pub unsafe fn run(mb: &Mailbox, code: &[u8], args: Args) -> Result<u32> {
+    if code.is_empty() || u32::MAX as usize <= code.len() {
+        return Err(Error::InvalidVpuCode { size: code.len() });
+    }
+    let mem = LockedMemory::new(
+        mb,
+        code.len() as u32,
+        4096,
+        memflag::Flags::MEM_FLAG_L1_NONALLOCATING,
+    )?;
+    mem.map()?.as_mut_slice()[..code.len()].copy_from_slice(code);
+    execute_code(mb, mem.bus_address(), args)
+}